@@ -0,0 +1,50 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use ilmen_http::http::{HTTPResponse, RouteTree};
+use ilmen_http::{RequestHandler, ResponseBuilder, Route, Routes, StatusCode, Verb};
+
+fn handler(_: &RequestHandler) -> HTTPResponse {
+    ResponseBuilder::new(StatusCode::OK, None).build()
+}
+
+fn routes() -> Routes {
+    let resources = ["users", "books", "authors", "reviews", "orders", "carts", "tags", "shelves"];
+    let verbs = [Verb::GET, Verb::POST, Verb::PUT, Verb::DELETE];
+    resources.iter()
+        .flat_map(|resource| [
+            format!("/api/v1/{}", resource),
+            format!("/api/v1/{}/{{id}}", resource),
+            format!("/api/v1/{}/{{id}}/history", resource),
+            format!("/api/v1/{}/{{id}}/history/{{version}}", resource),
+        ])
+        .flat_map(|template| verbs.iter().map(move |verb| Route::new(verb, &template, handler, false)))
+        .collect()
+}
+
+/// The linear scan used before the tree: first route of the verb matching segment by segment.
+fn linear_find<'a>(routes: &'a Routes, verb: &Verb, path: &str) -> Option<&'a Route> {
+    let request = path.split('/').collect::<Vec<&str>>();
+    routes.iter()
+        .filter(|route| route.verb == *verb)
+        .find(|route| {
+            let template = route.route.split('/').collect::<Vec<&str>>();
+            template.len() == request.len() && template.iter()
+                .zip(request.iter())
+                .all(|(template, request)| template.starts_with('{') || template == request)
+        })
+}
+
+fn bench_router(c: &mut Criterion) {
+    let routes = routes();
+    let tree = RouteTree::from(&routes);
+    let paths = ["/api/v1/users", "/api/v1/orders/42/history", "/api/v1/shelves/7/history/3", "/api/v1/unknown/1"];
+
+    c.bench_function("linear scan", |b| b.iter(|| {
+        paths.iter().for_each(|path| { black_box(linear_find(&routes, &Verb::DELETE, black_box(path))); })
+    }));
+    c.bench_function("route tree", |b| b.iter(|| {
+        paths.iter().for_each(|path| { black_box(tree.find(&Verb::DELETE, black_box(path))); })
+    }));
+}
+
+criterion_group!(benches, bench_router);
+criterion_main!(benches);
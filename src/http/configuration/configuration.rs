@@ -1,12 +1,24 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
 use crate::http::security::service::SecurityProtocol;
 
+use super::cors::Cors;
+
 
 #[derive(Clone)]
 pub struct Config {
     port: Port,
     ip: Ip,
     security: SecurityProtocol,
-    request_size: usize
+    security_schemes: HashMap<String, SecurityProtocol>,
+    request_size: usize,
+    keep_alive_timeout: Duration,
+    request_timeout: Duration,
+    max_requests: usize,
+    workers: usize,
+    server_name: Option<String>,
+    cors: Option<Cors>
 }
 
 
@@ -19,7 +31,14 @@ impl Default for Config {
             port: 7878, 
             ip: "0.0.0.0".to_string(),
             security: SecurityProtocol::None,
-            request_size: 10485760
+            security_schemes: HashMap::new(),
+            request_size: 10485760,
+            keep_alive_timeout: Duration::from_secs(5),
+            request_timeout: Duration::from_secs(30),
+            max_requests: 100,
+            workers: 5,
+            server_name: None,
+            cors: None
         }
     }
 }
@@ -33,10 +52,38 @@ impl Config {
         self.security.clone()
     }
 
+    pub fn security_scheme(&self, name: &str) -> Option<SecurityProtocol> {
+        self.security_schemes.get(name).cloned()
+    }
+
     pub fn request_size(&self) -> usize {
         self.request_size
     }
 
+    pub fn keep_alive_timeout(&self) -> Duration {
+        self.keep_alive_timeout
+    }
+
+    pub fn request_timeout(&self) -> Duration {
+        self.request_timeout
+    }
+
+    pub fn max_requests(&self) -> usize {
+        self.max_requests
+    }
+
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+
+    pub fn server_name(&self) -> Option<String> {
+        self.server_name.clone()
+    }
+
+    pub fn cors(&self) -> Option<Cors> {
+        self.cors.clone()
+    }
+
     pub fn initialize() -> Config {
         Config::default()
     }
@@ -51,9 +98,55 @@ impl Config {
         self
     }
 
+    /// Registers a scheme that routes can require by name, in addition to the default security.
+    pub fn with_security_scheme(&mut self, name: &str, security: &SecurityProtocol) -> &mut Self {
+        self.security_schemes.insert(name.to_string(), security.clone());
+        self
+    }
+
     pub fn with_adresse(&mut self, ip: &str, port: &Port) ->  &mut Self {
         self.ip = ip.to_string();
         self.port = *port;
         self
     }
+
+    /// How long an idle persistent connection waits for its next request before being closed.
+    /// Idle connections are closed sooner when every worker is busy and others are waiting.
+    pub fn with_keep_alive_timeout(&mut self, timeout: &Duration) -> &mut Self {
+        self.keep_alive_timeout = *timeout;
+        self
+    }
+
+    /// Total time to receive a request head and body once it started, however slowly it trickles in.
+    pub fn with_request_timeout(&mut self, timeout: &Duration) -> &mut Self {
+        self.request_timeout = *timeout;
+        self
+    }
+
+    /// Number of threads serving connections, one connection at a time each.
+    ///
+    /// # Panics
+    ///
+    /// `HttpServer::start` panics if it is zero.
+    pub fn with_workers(&mut self, workers: &usize) -> &mut Self {
+        self.workers = *workers;
+        self
+    }
+
+    /// Maximum number of requests served on a single connection before it is closed.
+    pub fn with_max_requests(&mut self, max_requests: &usize) -> &mut Self {
+        self.max_requests = *max_requests;
+        self
+    }
+
+    /// Value of the `Server` header added to every response that does not set one.
+    pub fn with_server_name(&mut self, server_name: &str) -> &mut Self {
+        self.server_name = Some(server_name.to_string());
+        self
+    }
+
+    pub fn with_cors(&mut self, cors: &Cors) -> &mut Self {
+        self.cors = Some(cors.clone());
+        self
+    }
 }
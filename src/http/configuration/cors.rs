@@ -0,0 +1,119 @@
+use crate::http::header::Headers;
+use crate::Verb;
+
+/// Cross-origin resource sharing policy, applied to preflight requests and actual responses.
+///
+/// An origin of `*` allows any origin. When credentials are allowed, the request origin is
+/// echoed back instead of `*`, as browsers require.
+#[derive(Clone, Default)]
+pub struct Cors {
+    allowed_origins: Vec<String>,
+    allowed_headers: Vec<String>,
+    allow_credentials: bool,
+    max_age: Option<u64>
+}
+
+impl Cors {
+    pub fn initialize() -> Cors {
+        Cors::default()
+    }
+
+    pub fn with_allowed_origins(&mut self, origins: &[&str]) -> &mut Self {
+        self.allowed_origins = origins.iter().map(|origin| origin.to_string()).collect();
+        self
+    }
+
+    pub fn with_allowed_headers(&mut self, headers: &[&str]) -> &mut Self {
+        self.allowed_headers = headers.iter().map(|header| header.to_string()).collect();
+        self
+    }
+
+    pub fn with_credentials(&mut self, allow_credentials: &bool) -> &mut Self {
+        self.allow_credentials = *allow_credentials;
+        self
+    }
+
+    /// How long, in seconds, browsers may cache the preflight response.
+    pub fn with_max_age(&mut self, max_age: &u64) -> &mut Self {
+        self.max_age = Some(*max_age);
+        self
+    }
+
+    /// Headers of a response to a request coming from `origin`, empty when the origin is not allowed.
+    pub fn response_headers(&self, origin: &str) -> Headers {
+        let allow_origin = match self.allows_any_origin() && !self.allow_credentials {
+            true => "*".to_string(),
+            false if self.allows_any_origin() || self.allowed_origins.iter().any(|allowed| allowed == origin) => origin.to_string(),
+            false => return vec![]
+        };
+
+        let mut headers = vec![("Access-Control-Allow-Origin".to_string(), allow_origin.clone())];
+        if allow_origin != "*" {
+            headers.push(("Vary".to_string(), "Origin".to_string()));
+        }
+        if self.allow_credentials {
+            headers.push(("Access-Control-Allow-Credentials".to_string(), "true".to_string()));
+        }
+        headers
+    }
+
+    /// Headers of a preflight response for a path accepting `verbs`.
+    pub fn preflight_headers(&self, origin: &str, verbs: &[Verb]) -> Headers {
+        let mut headers = self.response_headers(origin);
+        if headers.is_empty() {
+            return headers;
+        }
+
+        headers.push(("Access-Control-Allow-Methods".to_string(), verbs.iter().map(Verb::to_string).collect::<Vec<String>>().join(", ")));
+        if !self.allowed_headers.is_empty() {
+            headers.push(("Access-Control-Allow-Headers".to_string(), self.allowed_headers.join(", ")));
+        }
+        if let Some(max_age) = self.max_age {
+            headers.push(("Access-Control-Max-Age".to_string(), max_age.to_string()));
+        }
+        headers
+    }
+
+    fn allows_any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|origin| origin == "*")
+    }
+}
+
+
+// UNIT TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn response_headers_only_for_allowed_origins() {
+        let cors = Cors::initialize().with_allowed_origins(&["https://ilmen.fr"]).to_owned();
+
+        assert_eq!(cors.response_headers("https://ilmen.fr"), vec![
+            ("Access-Control-Allow-Origin".to_string(), "https://ilmen.fr".to_string()),
+            ("Vary".to_string(), "Origin".to_string())]);
+        assert_eq!(cors.response_headers("https://evil.com"), vec![]);
+    }
+
+    #[test]
+    fn any_origin_with_credentials_echoes_origin() {
+        let cors = Cors::initialize().with_allowed_origins(&["*"]).with_credentials(&true).to_owned();
+
+        assert_eq!(cors.response_headers("https://ilmen.fr").first(), Some(&("Access-Control-Allow-Origin".to_string(), "https://ilmen.fr".to_string())));
+    }
+
+    #[test]
+    fn preflight_headers() {
+        let cors = Cors::initialize()
+            .with_allowed_origins(&["*"])
+            .with_allowed_headers(&["Content-Type", "Authorization"])
+            .with_max_age(&600)
+            .to_owned();
+
+        assert_eq!(cors.preflight_headers("https://ilmen.fr", &[Verb::GET, Verb::OPTION]), vec![
+            ("Access-Control-Allow-Origin".to_string(), "*".to_string()),
+            ("Access-Control-Allow-Methods".to_string(), "GET, OPTIONS".to_string()),
+            ("Access-Control-Allow-Headers".to_string(), "Content-Type, Authorization".to_string()),
+            ("Access-Control-Max-Age".to_string(), "600".to_string())]);
+    }
+}
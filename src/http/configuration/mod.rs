@@ -1,5 +1,8 @@
 mod thread_pool;
+mod cors;
+#[allow(clippy::module_inception)]
 mod configuration;
 
 pub use configuration::Config;
-pub use thread_pool::ThreadPool;
\ No newline at end of file
+pub use cors::Cors;
+pub use thread_pool::{Backlog, ThreadPool};
\ No newline at end of file
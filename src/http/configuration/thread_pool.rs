@@ -1,11 +1,22 @@
 use std::{
-    sync::{mpsc, Arc, Mutex},
+    sync::{atomic::{AtomicUsize, Ordering}, mpsc, Arc, Mutex},
     thread,
 };
 
 pub struct ThreadPool {
     workers: Vec<Worker>,
     sender: Option<mpsc::Sender<Job>>,
+    backlog: Backlog,
+}
+
+/// Jobs waiting for a free worker, shared with the running jobs so they can make room.
+#[derive(Clone, Default)]
+pub struct Backlog(Arc<AtomicUsize>);
+
+impl Backlog {
+    pub fn is_empty(&self) -> bool {
+        self.0.load(Ordering::SeqCst) == 0
+    }
 }
 
 type Job = Box<dyn FnOnce() + Send + 'static>;
@@ -24,25 +35,32 @@ impl ThreadPool {
         let (sender, receiver) = mpsc::channel();
 
         let receiver = Arc::new(Mutex::new(receiver));
+        let backlog = Backlog::default();
 
         let mut workers = Vec::with_capacity(size);
 
         for id in 0..size {
-            workers.push(Worker::new(id, Arc::clone(&receiver)));
+            workers.push(Worker::new(id, Arc::clone(&receiver), backlog.clone()));
         }
 
         ThreadPool {
             workers,
             sender: Some(sender),
+            backlog,
         }
     }
 
+    pub fn backlog(&self) -> Backlog {
+        self.backlog.clone()
+    }
+
     pub fn execute<F>(&self, f: F)
     where
         F: FnOnce() + Send + 'static,
     {
         let job = Box::new(f);
 
+        self.backlog.0.fetch_add(1, Ordering::SeqCst);
         self.sender.as_ref().unwrap().send(job).unwrap();
     }
 }
@@ -67,12 +85,13 @@ struct Worker {
 }
 
 impl Worker {
-    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Job>>>) -> Worker {
+    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Job>>>, backlog: Backlog) -> Worker {
         let thread = thread::spawn(move || loop {
             let message = receiver.lock().unwrap().recv();
 
             match message {
                 Ok(job) => {
+                    backlog.0.fetch_sub(1, Ordering::SeqCst);
                     println!("Worker {id} got a job; executing.");
 
                     job();
@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use aes_gcm::{aead::{Aead, AeadCore, OsRng, Payload}, Aes256Gcm, Key, KeyInit, Nonce};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use super::header::Headers;
+
+pub type Cookies = HashMap<String, String>;
+
+const NONCE_SIZE : usize = 12;
+/// 9999-12-31T23:59:59Z, the last date an HTTP-date can hold.
+const LAST_HTTP_DATE : u64 = 253_402_300_799;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error("Invalid cookie {0}: {1}")]
+pub struct InvalidCookie(pub &'static str, pub String);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None
+}
+
+/// A cookie to send with `ResponseBuilder::cookie`, serialized as a `Set-Cookie` header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cookie {
+    name: String,
+    value: String,
+    path: Option<String>,
+    domain: Option<String>,
+    max_age: Option<u64>,
+    expires: Option<SystemTime>,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<SameSite>
+}
+
+impl Cookie {
+    /// Fails unless the name is a token and the value made of cookie-octets (RFC 6265 §4.1.1),
+    /// which excludes spaces, `"`, `,`, `;`, `\` and control characters.
+    pub fn new(name: &str, value: &str) -> Result<Cookie, InvalidCookie> {
+        if name.is_empty() || !name.bytes().all(is_token) {
+            return Err(InvalidCookie("name", name.to_string()));
+        }
+        if !value.bytes().all(is_cookie_octet) {
+            return Err(InvalidCookie("value", value.to_string()));
+        }
+
+        Ok(Cookie {
+            name: name.to_string(),
+            value: value.to_string(),
+            path: None,
+            domain: None,
+            max_age: None,
+            expires: None,
+            secure: false,
+            http_only: false,
+            same_site: None
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Fails on control characters and `;`, which would end the attribute.
+    pub fn path(mut self, path: &str) -> Result<Cookie, InvalidCookie> {
+        self.path = Some(attribute_value("path", path)?);
+        Ok(self)
+    }
+
+    /// Fails on control characters and `;`, which would end the attribute.
+    pub fn domain(mut self, domain: &str) -> Result<Cookie, InvalidCookie> {
+        self.domain = Some(attribute_value("domain", domain)?);
+        Ok(self)
+    }
+
+    /// Lifetime in seconds, `0` deleting the cookie.
+    pub fn max_age(mut self, seconds: u64) -> Cookie {
+        self.max_age = Some(seconds);
+        self
+    }
+
+    /// Fails on dates an HTTP-date cannot hold, before 1970 or after 9999.
+    pub fn expires(mut self, expires: SystemTime) -> Result<Cookie, InvalidCookie> {
+        if expires < UNIX_EPOCH || expires > UNIX_EPOCH + Duration::from_secs(LAST_HTTP_DATE) {
+            return Err(InvalidCookie("expires", format!("{:?}", expires)));
+        }
+        self.expires = Some(expires);
+        Ok(self)
+    }
+
+    pub fn secure(mut self) -> Cookie {
+        self.secure = true;
+        self
+    }
+
+    pub fn http_only(mut self) -> Cookie {
+        self.http_only = true;
+        self
+    }
+
+    /// `SameSite=None` is only accepted by browsers on secure cookies.
+    pub fn same_site(mut self, same_site: SameSite) -> Cookie {
+        self.same_site = Some(same_site);
+        self
+    }
+}
+
+impl fmt::Display for Cookie {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={}", self.name, self.value)?;
+        if let Some(path) = &self.path {
+            write!(f, "; Path={}", path)?;
+        }
+        if let Some(domain) = &self.domain {
+            write!(f, "; Domain={}", domain)?;
+        }
+        if let Some(max_age) = self.max_age {
+            write!(f, "; Max-Age={}", max_age)?;
+        }
+        if let Some(expires) = self.expires {
+            write!(f, "; Expires={}", httpdate::fmt_http_date(expires))?;
+        }
+        if self.secure {
+            write!(f, "; Secure")?;
+        }
+        if self.http_only {
+            write!(f, "; HttpOnly")?;
+        }
+        if let Some(same_site) = self.same_site {
+            write!(f, "; SameSite={:?}", same_site)?;
+        }
+        Ok(())
+    }
+}
+
+fn is_token(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+fn is_cookie_octet(byte: u8) -> bool {
+    matches!(byte, 0x21 | 0x23..=0x2B | 0x2D..=0x3A | 0x3C..=0x5B | 0x5D..=0x7E)
+}
+
+fn attribute_value(attribute: &'static str, value: &str) -> Result<String, InvalidCookie> {
+    match value.chars().any(|character| character.is_control() || character == ';') {
+        true => Err(InvalidCookie(attribute, value.to_string())),
+        false => Ok(value.to_string())
+    }
+}
+
+/// Cookies sent by the client, from every `Cookie` header. For a name sent twice, the first one
+/// wins, browsers sending the cookie with the most specific path first (RFC 6265 §5.4).
+pub fn parse_cookies(headers: &Headers) -> Cookies {
+    headers.iter()
+        .filter(|(header, _)| header.eq_ignore_ascii_case("Cookie"))
+        .flat_map(|(_, value)| value.split(';'))
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(name, value)| (name.trim().to_string(), unquote(value.trim()).to_string()))
+        .filter(|(name, _)| !name.is_empty())
+        .fold(Cookies::new(), |mut cookies, (name, value)| {
+            cookies.entry(name).or_insert(value);
+            cookies
+        })
+}
+
+fn unquote(value: &str) -> &str {
+    value.strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+/// Server secret signing or encrypting cookie values, so the client can neither forge nor
+/// (when encrypted) read them. The cookie name is bound to the value.
+#[derive(Clone)]
+pub struct CookieKey {
+    signing: Vec<u8>,
+    encryption: Vec<u8>
+}
+
+impl CookieKey {
+    pub fn new(secret: &[u8]) -> CookieKey {
+        let derive = |label: &[u8]| Sha256::new().chain_update(label).chain_update(secret).finalize().to_vec();
+        CookieKey { signing: derive(b"signing"), encryption: derive(b"encryption") }
+    }
+
+    /// Appends a signature to the value, the value itself stays readable.
+    pub fn sign(&self, mut cookie: Cookie) -> Cookie {
+        let signature = URL_SAFE_NO_PAD.encode(self.mac(&cookie.name, &cookie.value).finalize().into_bytes());
+        cookie.value = format!("{}.{}", cookie.value, signature);
+        cookie
+    }
+
+    /// The value of a cookie signed by `sign`, if the signature matches.
+    pub fn verify(&self, name: &str, value: &str) -> Option<String> {
+        let (value, signature) = value.rsplit_once('.')?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        self.mac(name, value)
+            .verify_slice(&signature)
+            .ok()
+            .map(|_| value.to_string())
+    }
+
+    /// Replaces the value by its authenticated encryption.
+    pub fn encrypt(&self, mut cookie: Cookie) -> Cookie {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let payload = Payload { msg: cookie.value.as_bytes(), aad: cookie.name.as_bytes() };
+        let encrypted = self.cipher().encrypt(&nonce, payload).expect("AES-GCM encrypts any cookie sized value");
+        cookie.value = URL_SAFE_NO_PAD.encode([nonce.as_slice(), &encrypted].concat());
+        cookie
+    }
+
+    /// The value of a cookie encrypted by `encrypt`, if it was not tampered with.
+    pub fn decrypt(&self, name: &str, value: &str) -> Option<String> {
+        let decoded = URL_SAFE_NO_PAD.decode(value).ok()?;
+        if decoded.len() < NONCE_SIZE {
+            return None;
+        }
+        let (nonce, encrypted) = decoded.split_at(NONCE_SIZE);
+        self.cipher()
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: encrypted, aad: name.as_bytes() })
+            .ok()
+            .and_then(|value| String::from_utf8(value).ok())
+    }
+
+    fn mac(&self, name: &str, value: &str) -> Hmac<Sha256> {
+        <Hmac<Sha256> as Mac>::new_from_slice(&self.signing)
+            .expect("HMAC accepts keys of any size")
+            .chain_update(name.as_bytes())
+            .chain_update(b"=")
+            .chain_update(value.as_bytes())
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.encryption))
+    }
+}
+
+
+// UNIT TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cookie_serializes_its_attributes() {
+        let cookie = Cookie::new("session", "42").unwrap()
+            .path("/").unwrap()
+            .domain("ilmen.fr").unwrap()
+            .max_age(3600)
+            .expires(UNIX_EPOCH + Duration::from_secs(0)).unwrap()
+            .secure()
+            .http_only()
+            .same_site(SameSite::Lax);
+
+        assert_eq!(cookie.to_string(), "session=42; Path=/; Domain=ilmen.fr; Max-Age=3600; Expires=Thu, 01 Jan 1970 00:00:00 GMT; Secure; HttpOnly; SameSite=Lax");
+        assert_eq!(Cookie::new("theme", "dark").unwrap().to_string(), "theme=dark");
+    }
+
+    #[test]
+    fn cookie_rejects_header_and_attribute_injection() {
+        assert_eq!(Cookie::new("session", "42\r\nSet-Cookie: admin=1"), Err(InvalidCookie("value", "42\r\nSet-Cookie: admin=1".to_string())));
+        assert_eq!(Cookie::new("session", "42; Domain=evil.com"), Err(InvalidCookie("value", "42; Domain=evil.com".to_string())));
+        assert_eq!(Cookie::new("a=b", "42"), Err(InvalidCookie("name", "a=b".to_string())));
+        assert_eq!(Cookie::new("", "42"), Err(InvalidCookie("name", "".to_string())));
+        assert!(Cookie::new("session", "42").unwrap().path("/; Max-Age=0").is_err());
+        assert!(Cookie::new("session", "42").unwrap().domain("ilmen.fr\r\n").is_err());
+        assert!(Cookie::new("session", "").is_ok());
+    }
+
+    #[test]
+    fn cookie_rejects_expiry_dates_without_http_date() {
+        let cookie = Cookie::new("session", "42").unwrap();
+
+        assert!(cookie.clone().expires(UNIX_EPOCH - Duration::from_secs(1)).is_err());
+        assert!(cookie.clone().expires(UNIX_EPOCH + Duration::from_secs(LAST_HTTP_DATE + 1)).is_err());
+        assert_eq!(cookie.expires(UNIX_EPOCH + Duration::from_secs(LAST_HTTP_DATE)).unwrap().to_string(),
+            "session=42; Expires=Fri, 31 Dec 9999 23:59:59 GMT");
+    }
+
+    #[test]
+    fn parse_cookies_from_every_cookie_header() {
+        let headers = vec![
+            ("Cookie".to_string(), "session=42; theme=\"dark\"; quote=\"open".to_string()),
+            ("cookie".to_string(), "lang=fr;broken; session=0".to_string()),
+            ("Cookie2".to_string(), "ignored=1".to_string())
+        ];
+
+        let cookies = parse_cookies(&headers);
+
+        assert_eq!(cookies.len(), 4);
+        assert_eq!(cookies.get("quote"), Some(&"\"open".to_string()));
+        assert_eq!(cookies.get("session"), Some(&"42".to_string()));
+        assert_eq!(cookies.get("theme"), Some(&"dark".to_string()));
+        assert_eq!(cookies.get("lang"), Some(&"fr".to_string()));
+    }
+
+    #[test]
+    fn signed_cookies_detect_tampering() {
+        let key = CookieKey::new(b"secret");
+        let signed = key.sign(Cookie::new("user", "toto").unwrap());
+
+        assert!(signed.value().starts_with("toto."));
+        assert_eq!(key.verify("user", signed.value()), Some("toto".to_string()));
+        assert_eq!(key.verify("admin", signed.value()), None);
+        assert_eq!(key.verify("user", &signed.value().replacen("toto", "tata", 1)), None);
+        assert_eq!(CookieKey::new(b"other").verify("user", signed.value()), None);
+    }
+
+    #[test]
+    fn encrypted_cookies_hide_their_value() {
+        let key = CookieKey::new(b"secret");
+        let encrypted = key.encrypt(Cookie::new("user", "toto").unwrap());
+
+        assert!(!encrypted.value().contains("toto"));
+        assert_eq!(key.decrypt("user", encrypted.value()), Some("toto".to_string()));
+        assert_eq!(key.decrypt("admin", encrypted.value()), None);
+        assert_eq!(CookieKey::new(b"other").decrypt("user", encrypted.value()), None);
+        assert_eq!(key.decrypt("user", "dG90bw"), None);
+    }
+}
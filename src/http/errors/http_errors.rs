@@ -1,7 +1,9 @@
 use thiserror::Error;
 
+use crate::Verb;
 
-#[derive(Error, Debug, PartialEq, Eq)]
+
+#[derive(Error, Debug, PartialEq, Eq, Clone)]
 pub enum HttpError
 {
     #[error("An error occured")]
@@ -13,7 +15,20 @@ pub enum HttpError
     #[error("Authorization Error: {0}")]
     UnauthorizedError(String),
 
+    /// A 401 carrying the `WWW-Authenticate` challenge to send back, then the reason.
+    #[error("Authentication Required: {1}")]
+    AuthenticationRequired(String, String),
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
     #[error("Bad Request: {0}")]
-    BadRequest(String)
+    BadRequest(String),
+
+    #[error("Payload Too Large: {0}")]
+    PayloadTooLarge(String),
+
+    #[error("Method Not Allowed, allowed: {0:?}")]
+    MethodNotAllowed(Vec<Verb>)
 
 }
\ No newline at end of file
@@ -1,7 +1,7 @@
 use core::fmt;
 use std::{error::Error, str::Utf8Error};
 
-use crate::{HTTPResponse, ResponseBuilder};
+use crate::{HTTPResponse, ResponseBuilder, StatusCode};
 
 
 #[derive(Debug)]
@@ -43,6 +43,6 @@ impl From<Utf8Error> for InternalError {
 
 impl From<InternalError> for HTTPResponse {
     fn from(_: InternalError) -> Self {
-        ResponseBuilder::new(500,Some("Internal Server Error".to_string())).build()
+        ResponseBuilder::new(StatusCode::INTERNAL_SERVER_ERROR,Some("Internal Server Error".to_string())).build()
     }
 }
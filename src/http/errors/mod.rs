@@ -1,4 +1,5 @@
 mod internal;
+#[allow(dead_code)]
 mod security;
 pub mod http_errors;
 
@@ -4,20 +4,33 @@ mod router;
 mod requests;
 mod responses;
 mod server;
+mod state;
 pub mod security;
 pub mod header;
+pub mod cookie;
 
 pub use router::Routes;
+pub use router::RouteTree;
 pub use router::Route;
+pub use router::Router;
 pub use router::RequestHandler;
+pub use router::Handler;
+pub use router::Middleware;
+pub use router::HandlerError;
+pub use router::IntoHandlerResult;
+
+pub use state::State;
 
 pub use configuration::Config;
+pub use configuration::Cors;
 
 pub use requests::HTTPRequest;
 pub use requests::Verb;
 
 pub use responses::HTTPResponse;
 pub use responses::ResponseBuilder;
+pub use responses::StatusCode;
+pub use responses::InvalidStatusCode;
 
 pub use errors::http_errors::HttpError;
 
@@ -0,0 +1,114 @@
+use std::io::BufRead;
+use std::io::Read;
+
+use crate::http::errors::http_errors::HttpError;
+use crate::http::header::Headers;
+
+/// Decodes a `Transfer-Encoding: chunked` body, returning the payload and the trailer fields.
+/// Chunk extensions are accepted and ignored. Everything read, framing included, counts against `max_size`.
+pub fn read_chunked<R: BufRead>(reader: &mut R, max_size: usize) -> Result<(Vec<u8>, Headers), HttpError> {
+    let mut consumed = 0;
+    let mut body = vec![];
+
+    loop {
+        let line = read_line(reader, max_size, &mut consumed)?;
+        let size = parse_chunk_size(&line)?;
+        if size == 0 {
+            break;
+        }
+
+        if size > max_size - consumed {
+            return Err(HttpError::PayloadTooLarge(format!("Chunked body exceeds {} bytes", max_size)));
+        }
+        consumed += size;
+
+        let mut chunk = vec![0; size];
+        reader.read_exact(&mut chunk)
+            .map_err(|_| HttpError::BadRequest("Incomplete chunk".to_string()))?;
+        body.extend(chunk);
+
+        if !read_line(reader, max_size, &mut consumed)?.is_empty() {
+            return Err(HttpError::BadRequest("Missing chunk terminator".to_string()));
+        }
+    }
+
+    let mut trailers = vec![];
+    loop {
+        let line = read_line(reader, max_size, &mut consumed)?;
+        if line.is_empty() {
+            break;
+        }
+        let (key, value) = line.split_once(':')
+            .ok_or(HttpError::BadRequest(format!("Invalid trailer: {}", line)))?;
+        trailers.push((key.trim().to_string(), value.trim().to_string()));
+    }
+
+    Ok((body, trailers))
+}
+
+/// `chunk-size = 1*HEXDIG`, nothing else: no sign, which `from_str_radix` would accept.
+fn parse_chunk_size(line: &str) -> Result<usize, HttpError> {
+    let size = line.split(';').next().unwrap_or_default().trim();
+    Some(size)
+        .filter(|size| !size.is_empty() && size.bytes().all(|digit| digit.is_ascii_hexdigit()))
+        .and_then(|size| usize::from_str_radix(size, 16).ok())
+        .ok_or(HttpError::BadRequest(format!("Invalid chunk size: {}", size)))
+}
+
+fn read_line<R: BufRead>(reader: &mut R, max_size: usize, consumed: &mut usize) -> Result<String, HttpError> {
+    let mut line = vec![];
+    let limit = (max_size - *consumed).saturating_add(1) as u64;
+    reader.by_ref().take(limit).read_until(b'\n', &mut line)
+        .map_err(|error| HttpError::BadRequest(error.to_string()))?;
+
+    *consumed += line.len();
+    if *consumed > max_size {
+        return Err(HttpError::PayloadTooLarge(format!("Chunked body exceeds {} bytes", max_size)));
+    }
+    if !line.ends_with(b"\n") {
+        return Err(HttpError::BadRequest("Incomplete chunked body".to_string()));
+    }
+
+    String::from_utf8(line)
+        .map(|line| line.trim_end_matches(['\r', '\n']).to_string())
+        .map_err(|error| HttpError::BadRequest(error.to_string()))
+}
+
+
+// UNIT TEST
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn read_chunked_with_extensions_and_trailers() {
+        let mut reader = Cursor::new("4;name=value\r\nWiki\r\n5\r\npedia\r\n0\r\nExpires: never\r\n\r\n");
+
+        let (body, trailers) = read_chunked(&mut reader, 1024).unwrap();
+
+        assert_eq!(body, b"Wikipedia".to_vec());
+        assert_eq!(trailers, vec![("Expires".to_string(), "never".to_string())]);
+    }
+
+    #[test]
+    fn read_chunked_invalid_size() {
+        ["zz", "+4", "-0", ""].iter().for_each(|size| {
+            let mut reader = Cursor::new(format!("{}\r\nWiki\r\n0\r\n\r\n", size));
+
+            let error = read_chunked(&mut reader, 1024).unwrap_err();
+
+            assert_eq!(error, HttpError::BadRequest(format!("Invalid chunk size: {}", size)));
+        });
+    }
+
+    #[test]
+    fn read_chunked_too_large() {
+        let mut reader = Cursor::new("10\r\n0123456789abcdef\r\n0\r\n\r\n");
+
+        let error = read_chunked(&mut reader, 10).unwrap_err();
+
+        assert!(matches!(error, HttpError::PayloadTooLarge(_)));
+    }
+}
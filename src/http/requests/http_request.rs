@@ -1,41 +1,45 @@
 use std::collections::HashMap;
+use std::io::Cursor;
 use std::str::FromStr;
-use std::usize;
-use std::str;
 use log::trace;
 
 use crate::http::errors::http_errors::HttpError;
 use crate::http::header::Headers;
+use crate::http::security::principal::Principal;
 use crate::Verb;
 
-#[derive(Clone)]
+use super::reader::read_request;
+
+#[derive(Clone, Debug)]
 pub struct HTTPRequest {
     pub protocol: Protocol,
     pub verb: Verb,
     pub resource: Resource, 
     pub query_params: Option<QueryParams>,
     pub headers: Option<Headers>,
-    pub body: Option<Body>
+    pub body: Option<Body>,
+    pub trailers: Option<Headers>,
+    /// Only set by the route security, read through `principal()`.
+    pub(crate) principal: Option<Principal>
 }
 
 pub type Resource = String; 
 pub type Protocol = String; 
 pub type QueryParams = HashMap<String, String>;
-type Body = String;
+pub(super) type Body = Vec<u8>;
 
 impl Default for HTTPRequest {
     fn default() -> Self {
-        Self { protocol: Default::default(), verb: Verb::GET, resource: "/".to_string(), query_params: Default::default(), headers: Default::default(), body: Default::default() }
+        Self { protocol: Default::default(), verb: Verb::GET, resource: "/".to_string(), query_params: Default::default(), headers: Default::default(), body: Default::default(), trailers: Default::default(), principal: Default::default() }
     }
 }
 
 impl TryFrom<Vec<u8>> for HTTPRequest {
     type Error = HttpError;
     fn try_from(value: Vec<u8>) -> Result<Self, HttpError> {
-        str::from_utf8(&value)
-        .map_err(|error| 
-            HttpError::BadRequest(error.to_string()))
-        .map(HTTPRequest::try_from)?
+        let size = value.len();
+        read_request(&mut Cursor::new(value), size)?
+            .ok_or(HttpError::BadRequest("Empty request".to_string()))
     }
 }
 
@@ -53,88 +57,114 @@ impl TryFrom<Vec<String>> for Verb {
 impl TryFrom<&str> for HTTPRequest {
     type Error = HttpError;
     fn try_from(buffer: &str) -> Result<Self, HttpError> {
-        let parsed_request = parse(buffer);
-        
-        let decomposed_start_line = parsed_request.first()
-            .ok_or(HttpError::BadRequest("Mising ressources".to_string()))?
-            .split(' ')
-            .take(3)
-            .map(String::from)
-            .collect::<Vec<String>>();
-
-        let verb = Verb::try_from(decomposed_start_line.clone())?;
+        HTTPRequest::try_from(buffer.as_bytes().to_vec())
+    }
+}
 
-        let protocol = decomposed_start_line
-            .get(2)
-            .ok_or(HttpError::BadRequest("Missing protocol".to_string()))?
-            .to_string();
+/// Parses the request line and headers. The body is left empty, it is read separately by the reader.
+pub(super) fn parse_head(head: &str) -> Result<HTTPRequest, HttpError> {
+    let parsed_request = parse(head);
+    
+    let decomposed_start_line = parsed_request.first()
+        .ok_or(HttpError::BadRequest("Mising ressources".to_string()))?
+        .split(' ')
+        .take(3)
+        .map(String::from)
+        .collect::<Vec<String>>();
 
+    let verb = Verb::try_from(decomposed_start_line.clone())?;
 
-        let requested_resource = decomposed_start_line
-            .get(1)
-            .ok_or(HttpError::BadRequest("No ressource".to_string()))
-            .map(|str| str.split("?").collect::<Vec<&str>>())?; 
+    let protocol = decomposed_start_line
+        .get(2)
+        .ok_or(HttpError::BadRequest("Missing protocol".to_string()))?
+        .to_string();
 
-        let resource = requested_resource.first().ok_or(HttpError::BadRequest("Missing path".to_string()))?;
 
-        let query_params = requested_resource.get(1)
-            .map(|params| params.split("&").collect::<Vec<&str>>())
-            .map(|vec_params| 
-                vec_params.iter()
-                    .map(|couple| couple
-                        .split_once("=")
-                        .unwrap_or((couple, "")))
-                    .map(|(a,b)|(a.to_string(), b.to_string()))
-                    .collect::<QueryParams>());
+    let requested_resource = decomposed_start_line
+        .get(1)
+        .ok_or(HttpError::BadRequest("No ressource".to_string()))
+        .map(|str| str.split("?").collect::<Vec<&str>>())?; 
 
+    let resource = requested_resource.first().ok_or(HttpError::BadRequest("Missing path".to_string()))?;
 
-        let headers = extract_headers(parsed_request.clone());
-        trace!("Headers: {:?}", headers);
-        
-        let body = get_header(&headers, "Content-Length")
-            .map(|(_, length)| length.parse::<usize>())
-            .transpose()
-            .map_err(|_| HttpError::BadRequest("Content Length not a number".to_string()))?
-            .map(|length| extract_body(buffer, length));
+    let query_params = requested_resource.get(1)
+        .map(|params| params.split("&").collect::<Vec<&str>>())
+        .map(|vec_params| 
+            vec_params.iter()
+                .map(|couple| couple
+                    .split_once("=")
+                    .unwrap_or((couple, "")))
+                .map(|(a,b)|(a.to_string(), b.to_string()))
+                .collect::<QueryParams>());
 
-        Ok (HTTPRequest {protocol, 
-            verb, 
-            query_params, 
-            headers: Some(headers), 
-            body,
-            resource: resource.to_string()})
-    }
-}
 
+    let headers = extract_headers(parsed_request.clone());
+    trace!("Headers: {:?}", headers);
 
-fn extract_body(request : &str, content_length: usize) -> Body {
-    return request.split_once("\r\n\r\n")
-        .map(|(_, b)| b[0..content_length].to_string())
-        .unwrap_or_default();
+    Ok (HTTPRequest {protocol, 
+        verb, 
+        query_params, 
+        headers: Some(headers), 
+        body: None,
+        trailers: None,
+        principal: None,
+        resource: resource.to_string()})
 }
 
 
 fn parse(buffer : &str) -> Vec<String> {
-    return buffer
+    buffer
         .trim_matches(char::from(0))
         .split("\r\n")
         .map(|str| str.to_string())
-        .collect::<Vec<String>>();
+        .collect::<Vec<String>>()
 }
 
 impl HTTPRequest {
+    /// The caller, once the route security authenticated it.
+    pub fn principal(&self) -> Option<&Principal> {
+        self.principal.as_ref()
+    }
+
     pub fn get_header(&self, key: &str) -> Option<(String, String)> {
         self.headers.clone().unwrap_or_default()
             .iter()
-            .find(|(header, _)| header.to_lowercase().starts_with(&key.to_lowercase())).cloned()
+            .find(|(header, _)| header.eq_ignore_ascii_case(key)).cloned()
+    }
+
+    pub fn content_length(&self) -> Result<Option<usize>, HttpError> {
+        self.get_header("Content-Length")
+            .map(|(_, length)| length.parse::<usize>())
+            .transpose()
+            .map_err(|_| HttpError::BadRequest("Content Length not a number".to_string()))
     }
-}
 
-fn get_header(headers: &Headers, key: &str) -> Option<(String, String)> {
-    headers
-        .iter()
-        .find(|(header, _)| header.to_lowercase().starts_with(&key.to_lowercase()))
-        .cloned()
+    pub fn get_trailer(&self, key: &str) -> Option<(String, String)> {
+        self.trailers.clone().unwrap_or_default()
+            .iter()
+            .find(|(trailer, _)| trailer.eq_ignore_ascii_case(key)).cloned()
+    }
+
+    /// Whether the body is sent with `Transfer-Encoding: chunked`, which takes precedence over `Content-Length`.
+    pub fn is_chunked(&self) -> bool {
+        self.get_header("Transfer-Encoding")
+            .and_then(|(_, value)| value.rsplit(',').next().map(|coding| coding.trim().eq_ignore_ascii_case("chunked")))
+            .unwrap_or(false)
+    }
+
+    /// Whether the client expects the connection to stay open after this request.
+    /// HTTP/1.1 defaults to keep-alive, HTTP/1.0 to close, unless a `Connection` header says otherwise.
+    pub fn keep_alive(&self) -> bool {
+        let connection = self.get_header("Connection")
+            .map(|(_, value)| value.to_lowercase())
+            .unwrap_or_default();
+        let tokens = connection.split(',').map(str::trim).collect::<Vec<&str>>();
+
+        if tokens.contains(&"close") {
+            return false;
+        }
+        tokens.contains(&"keep-alive") || self.protocol == "HTTP/1.1"
+    }
 }
 
 fn extract_headers(request : Vec<String>) -> Headers {
@@ -171,7 +201,7 @@ mod tests {
         assert_eq!(http_request.verb, Verb::POST);
         assert_eq!(http_request.resource, "rappel/1");
         assert_eq!(http_request.query_params, Some(expected_query_params));
-        assert_eq!(http_request.body, Some("toto\r\ntata".to_string()));
+        assert_eq!(http_request.body, Some(b"toto\r\ntata".to_vec()));
         assert_eq!(http_request.headers, Some(vec![("Content-Length".to_string(), "10".to_string())]))
     }
 
@@ -184,6 +214,15 @@ mod tests {
         assert!(request.is_ok());
     }
 
+    #[test]
+    fn request_try_from_does_not_trust_the_content_length() {
+        let huge = HTTPRequest::try_from("POST / HTTP/1.1\r\nContent-Length: 100000000000000\r\n\r\ntoto");
+        let overflowing = HTTPRequest::try_from(format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\ntoto", u64::MAX).as_str());
+
+        assert!(matches!(huge, Err(HttpError::PayloadTooLarge(_))));
+        assert!(matches!(overflowing, Err(HttpError::PayloadTooLarge(_))));
+    }
+
 
     #[test]
     fn extract_headers_ok() {
@@ -193,5 +232,26 @@ mod tests {
         
         assert_eq!(request, vec![("Content-Length".to_string(), "1".to_string()),("Content-type".to_string(), "x and y".to_string())]);
     }
+
+    #[test]
+    fn get_header_matches_the_whole_name() {
+        let request = HTTPRequest::try_from("GET / HTTP/1.1\r\nX-Api-Key-Id: 1\r\nx-api-key: toto\r\n\r\n").unwrap();
+
+        assert_eq!(request.get_header("X-Api-Key"), Some(("x-api-key".to_string(), "toto".to_string())));
+        assert_eq!(request.get_header("X-Api"), None);
+    }
+
+    #[test]
+    fn keep_alive_depends_on_protocol_and_connection_header() {
+        let combinations = [
+            ("GET / HTTP/1.1\r\n\r\n", true),
+            ("GET / HTTP/1.1\r\nConnection: close\r\n\r\n", false),
+            ("GET / HTTP/1.0\r\n\r\n", false),
+            ("GET / HTTP/1.0\r\nConnection: Keep-Alive\r\n\r\n", true),
+        ];
+
+        combinations.iter().for_each(|(buffer, expected)| 
+            assert_eq!(HTTPRequest::try_from(*buffer).unwrap().keep_alive(), *expected, "{}", buffer));
+    }
 }
 
@@ -1,6 +1,9 @@
 mod verb;
+mod chunked;
 mod http_request;
+mod reader;
 
 
 pub use http_request::HTTPRequest;
-pub use verb::Verb;
\ No newline at end of file
+pub use verb::Verb;
+pub use reader::read_request;
\ No newline at end of file
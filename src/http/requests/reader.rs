@@ -0,0 +1,186 @@
+use std::io::BufRead;
+use std::io::Read;
+
+use crate::http::errors::http_errors::HttpError;
+
+use super::chunked::read_chunked;
+use super::http_request::{parse_head, Body, HTTPRequest};
+
+/// Reads a single request from the stream: the head up to the empty line, then either the
+/// chunked body or exactly `Content-Length` bytes of body, whatever the number of reads it takes.
+///
+/// Framing that cannot be trusted (RFC 9112 §6.1) is a bad request, after which the connection
+/// must be closed: `Transfer-Encoding` together with `Content-Length`, or not ending with `chunked`.
+///
+/// Returns `None` when the peer closed (or timed out) before sending anything.
+pub fn read_request<R: BufRead>(reader: &mut R, max_size: usize) -> Result<Option<HTTPRequest>, HttpError> {
+    let head = match read_head(reader, max_size)? {
+        Some(head) => head,
+        None => return Ok(None)
+    };
+
+    let mut request = parse_head(&head)?;
+    if request.get_header("Transfer-Encoding").is_some() {
+        if request.get_header("Content-Length").is_some() {
+            return Err(HttpError::BadRequest("Both Transfer-Encoding and Content-Length".to_string()));
+        }
+        if !request.is_chunked() {
+            return Err(HttpError::BadRequest("Transfer-Encoding must end with chunked".to_string()));
+        }
+    }
+
+    if request.is_chunked() {
+        let (body, trailers) = read_chunked(reader, max_size)?;
+        request.body = Some(body);
+        request.trailers = Some(trailers);
+    } else {
+        request.body = request.content_length()?
+            .map(|length| read_body(reader, length, max_size))
+            .transpose()?;
+    }
+
+    Ok(Some(request))
+}
+
+fn read_head<R: BufRead>(reader: &mut R, max_size: usize) -> Result<Option<String>, HttpError> {
+    let mut head: Vec<u8> = vec![];
+    loop {
+        let mut line = vec![];
+        let limit = (max_size - head.len()).saturating_add(1) as u64;
+        let read = match reader.by_ref().take(limit).read_until(b'\n', &mut line) {
+            Ok(read) => read,
+            Err(_) if head.is_empty() => return Ok(None),
+            Err(error) => return Err(HttpError::BadRequest(error.to_string()))
+        };
+
+        if read == 0 {
+            return match head.is_empty() {
+                true => Ok(None),
+                false => Err(HttpError::BadRequest("Incomplete request head".to_string()))
+            };
+        }
+
+        let is_empty_line = line == b"\r\n" || line == b"\n";
+        if head.is_empty() && is_empty_line {
+            continue;
+        }
+
+        head.extend(line);
+        if head.len() > max_size {
+            return Err(HttpError::PayloadTooLarge(format!("Request head exceeds {} bytes", max_size)));
+        }
+
+        if is_empty_line {
+            break;
+        }
+    }
+
+    String::from_utf8(head)
+        .map(Some)
+        .map_err(|error| HttpError::BadRequest(error.to_string()))
+}
+
+fn read_body<R: Read>(reader: &mut R, length: usize, max_size: usize) -> Result<Body, HttpError> {
+    if length > max_size {
+        return Err(HttpError::PayloadTooLarge(format!("Body of {} bytes exceeds {} bytes", length, max_size)));
+    }
+
+    // Grown while reading, the announced length is not trusted for the allocation
+    let mut body = vec![];
+    match reader.take(length as u64).read_to_end(&mut body) {
+        Ok(read) if read == length => Ok(body),
+        _ => Err(HttpError::BadRequest("Body shorter than Content-Length".to_string()))
+    }
+}
+
+
+// UNIT TEST
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn reader(request: &str) -> BufReader<Cursor<Vec<u8>>> {
+        BufReader::with_capacity(3, Cursor::new(request.as_bytes().to_vec()))
+    }
+
+    #[test]
+    fn read_request_body_over_several_reads() {
+        let mut reader = reader("POST /book HTTP/1.1\r\nContent-Length: 13\r\n\r\nhello, world!");
+
+        let request = read_request(&mut reader, 1024).unwrap().unwrap();
+
+        assert_eq!(request.body, Some(b"hello, world!".to_vec()));
+    }
+
+    #[test]
+    fn read_request_pipelined_requests() {
+        let mut reader = reader("POST /a HTTP/1.1\r\nContent-Length: 2\r\n\r\nokGET /b HTTP/1.1\r\n\r\n");
+
+        let first = read_request(&mut reader, 1024).unwrap().unwrap();
+        let second = read_request(&mut reader, 1024).unwrap().unwrap();
+
+        assert_eq!(first.body, Some(b"ok".to_vec()));
+        assert_eq!(second.resource, "/b");
+        assert!(read_request(&mut reader, 1024).unwrap().is_none());
+    }
+
+    #[test]
+    fn read_request_chunked_body_with_trailers() {
+        let mut reader = reader("POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nWiki\r\n5\r\npedia\r\n0\r\nChecksum: abc\r\n\r\n");
+
+        let request = read_request(&mut reader, 1024).unwrap().unwrap();
+
+        assert_eq!(request.body, Some(b"Wikipedia".to_vec()));
+        assert_eq!(request.get_trailer("checksum"), Some(("Checksum".to_string(), "abc".to_string())));
+    }
+
+    #[test]
+    fn read_request_rejects_ambiguous_framing() {
+        let mut both = reader("POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\nContent-Length: 3\r\n\r\n4\r\nWiki\r\n0\r\n\r\n");
+        let mut not_chunked = reader("POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked, gzip\r\n\r\n");
+
+        assert_eq!(read_request(&mut both, 1024).unwrap_err(), HttpError::BadRequest("Both Transfer-Encoding and Content-Length".to_string()));
+        assert_eq!(read_request(&mut not_chunked, 1024).unwrap_err(), HttpError::BadRequest("Transfer-Encoding must end with chunked".to_string()));
+    }
+
+    #[test]
+    fn read_request_binary_body() {
+        let mut request = b"POST /image HTTP/1.1\r\nContent-Length: 4\r\n\r\n".to_vec();
+        request.extend([0x89, 0x50, 0xff, 0x00]);
+        let mut reader = BufReader::with_capacity(3, Cursor::new(request));
+
+        let request = read_request(&mut reader, 1024).unwrap().unwrap();
+
+        assert_eq!(request.body, Some(vec![0x89, 0x50, 0xff, 0x00]));
+    }
+
+    #[test]
+    fn read_request_body_too_large() {
+        let mut reader = reader("POST /book HTTP/1.1\r\nContent-Length: 2048\r\n\r\n");
+
+        let error = read_request(&mut reader, 1024).unwrap_err();
+
+        assert!(matches!(error, HttpError::PayloadTooLarge(_)));
+    }
+
+    #[test]
+    fn read_request_short_body() {
+        let mut reader = reader("POST /book HTTP/1.1\r\nContent-Length: 10\r\n\r\ntoto");
+
+        let error = read_request(&mut reader, 1024).unwrap_err();
+
+        assert_eq!(error, HttpError::BadRequest("Body shorter than Content-Length".to_string()));
+    }
+
+    #[test]
+    fn read_request_short_body_announcing_a_huge_length() {
+        let mut reader = reader("POST /book HTTP/1.1\r\nContent-Length: 100000000000000\r\n\r\ntoto");
+
+        let error = read_request(&mut reader, usize::MAX).unwrap_err();
+
+        assert_eq!(error, HttpError::BadRequest("Body shorter than Content-Length".to_string()));
+    }
+}
@@ -12,6 +12,7 @@ pub enum Verb {
     PUT,
     DELETE,
     PATCH,
+    #[strum(serialize = "OPTIONS")]
     OPTION
 }
 
@@ -0,0 +1,82 @@
+use std::io::{self, Read, Write};
+
+const CHUNK_SIZE: usize = 8192;
+
+/// Payload of a response: either held in memory, or streamed to the client with
+/// `Transfer-Encoding: chunked` as it is produced.
+pub enum Body {
+    Bytes(Vec<u8>),
+    Reader(Box<dyn Read + Send>),
+    Chunks(Box<dyn Iterator<Item = Vec<u8>> + Send>)
+}
+
+impl Body {
+    /// Writes the payload, framing streamed bodies as chunks when `chunked` is set.
+    pub(crate) fn write_to<W: Write>(self, writer: &mut W, chunked: bool) -> io::Result<()> {
+        match self {
+            Body::Bytes(bytes) => writer.write_all(&bytes),
+            Body::Reader(mut reader) => {
+                let mut buffer = vec![0; CHUNK_SIZE];
+                loop {
+                    let read = reader.read(&mut buffer)?;
+                    if read == 0 {
+                        break;
+                    }
+                    write_chunk(writer, &buffer[..read], chunked)?;
+                }
+                write_last_chunk(writer, chunked)
+            },
+            Body::Chunks(chunks) => {
+                for chunk in chunks {
+                    write_chunk(writer, &chunk, chunked)?;
+                }
+                write_last_chunk(writer, chunked)
+            }
+        }
+    }
+}
+
+fn write_chunk<W: Write>(writer: &mut W, chunk: &[u8], chunked: bool) -> io::Result<()> {
+    if !chunked {
+        return writer.write_all(chunk);
+    }
+    if chunk.is_empty() {
+        return Ok(());
+    }
+    write!(writer, "{:X}\r\n", chunk.len())?;
+    writer.write_all(chunk)?;
+    writer.write_all(b"\r\n")
+}
+
+fn write_last_chunk<W: Write>(writer: &mut W, chunked: bool) -> io::Result<()> {
+    match chunked {
+        true => writer.write_all(b"0\r\n\r\n"),
+        false => Ok(())
+    }
+}
+
+
+// UNIT TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_reader_as_chunks() {
+        let mut output = vec![];
+
+        Body::Reader(Box::new("hello world".as_bytes())).write_to(&mut output, true).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "B\r\nhello world\r\n0\r\n\r\n");
+    }
+
+    #[test]
+    fn write_iterator_as_chunks_skipping_empty_ones() {
+        let mut output = vec![];
+        let lines = vec![b"id;name\n".to_vec(), vec![], b"1;toto\n".to_vec()];
+
+        Body::Chunks(Box::new(lines.into_iter())).write_to(&mut output, true).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "8\r\nid;name\n\r\n7\r\n1;toto\n\r\n0\r\n\r\n");
+    }
+}
@@ -1,56 +1,132 @@
 use std::fmt;
+use std::io::{self, Read, Write};
+use std::time::SystemTime;
 
-use crate::http::{errors::http_errors::HttpError, header::{HeaderKey, HeaderValue, Headers}};
+use crate::http::{cookie::Cookie, errors::http_errors::HttpError, header::{HeaderKey, HeaderValue, Headers}};
+use crate::Verb;
+
+use super::body::Body;
+use super::status_code::StatusCode;
 
 const PROTOCOL : &str= "HTTP/1.1";
 
 pub struct HTTPResponse {
-    code: i32,
-    body: Option<String>,
-    headers: Headers
+    code: StatusCode,
+    body: Option<Body>,
+    headers: Headers,
+    close_delimited: bool
 }
 
 impl HTTPResponse {
-    pub fn code(&self) -> i32 {
+    pub fn code(&self) -> StatusCode {
         self.code
     }    
+
+    pub fn headers(&self) -> Headers {
+        self.headers.clone()
+    }
+
+    pub fn get_header(&self, key: &str) -> Option<HeaderValue> {
+        self.headers.iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.clone())
+    }
+
+    /// Sets the header, replacing any previous value.
+    pub fn put_header(&mut self, key: &str, value: &str) {
+        self.headers.retain(|(header, _)| !header.eq_ignore_ascii_case(key));
+        self.headers.push((key.to_string(), value.to_string()));
+    }
+
+    /// Adds the header unless the handler already set it.
+    pub(crate) fn put_default_header(&mut self, key: &str, value: &str) {
+        if self.get_header(key).is_none() {
+            self.headers.push((key.to_string(), value.to_string()));
+        }
+    }
+
+    /// Adds the value to a comma separated list header such as `Vary`, unless already listed.
+    pub(crate) fn add_to_header(&mut self, key: &str, value: &str) {
+        match self.get_header(key) {
+            Some(current) if current.split(',').any(|listed| listed.trim().eq_ignore_ascii_case(value)) => (),
+            Some(current) => self.put_header(key, &format!("{}, {}", current, value)),
+            None => self.headers.push((key.to_string(), value.to_string()))
+        }
+    }
+
+    /// Sends a streamed body without a `Content-Length` as is, ended by closing the connection,
+    /// for clients that do not support chunked encoding. Returns whether the connection must close.
+    pub(crate) fn without_chunked_encoding(&mut self) -> bool {
+        self.close_delimited = matches!(self.body, Some(Body::Reader(_)) | Some(Body::Chunks(_))) && self.get_header("Content-Length").is_none();
+        self.close_delimited
+    }
+
+    /// Writes the response on the wire. Streamed bodies are sent with `Transfer-Encoding: chunked`,
+    /// unless the handler gave their `Content-Length` or chunked encoding was disabled.
+    /// 1xx, 204 and 304 responses never have a body.
+    pub fn write_to<W: Write>(self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(self.head().as_bytes())?;
+        let chunked = self.is_chunked();
+        match self.body {
+            Some(body) if !has_no_content(self.code) => body.write_to(writer, chunked),
+            _ => Ok(())
+        }
+    }
+
+    fn is_chunked(&self) -> bool {
+        matches!(self.body, Some(Body::Reader(_)) | Some(Body::Chunks(_))) && self.get_header("Content-Length").is_none() && !self.close_delimited
+            && !has_no_content(self.code)
+    }
+
+    /// A 304 keeps the `Content-Length` given by the handler, the one of the selected representation.
+    /// 1xx and 204 responses must not have any framing header (RFC 9110 §8.6).
+    fn head(&self) -> String {
+        let mut head = HTTPResponse { code: self.code, body: None, headers: self.headers.clone(), close_delimited: false };
+        match &self.body {
+            _ if self.code == StatusCode::NOT_MODIFIED => (),
+            _ if has_no_content(self.code) => head.headers
+                .retain(|(key, _)| !key.eq_ignore_ascii_case("Content-Length") && !key.eq_ignore_ascii_case("Transfer-Encoding")),
+            _ if self.is_chunked() => head.put_header("Transfer-Encoding", "chunked"),
+            Some(Body::Bytes(bytes)) => head.put_header("Content-Length", &bytes.len().to_string()),
+            Some(_) => (),
+            None => head.put_header("Content-Length", "0")
+        };
+        head.put_default_header("Date", &httpdate::fmt_http_date(SystemTime::now()));
+
+        let headers= head.headers.iter()
+            .map(|(key, value)| key.to_string() + ": " + value + "\r\n")
+            .collect::<String>();
+        format!("{}\r\n{}\r\n", construct_status_line(self.code), headers)
+    }
 }
 
 impl Default for HTTPResponse {
     fn default() -> Self {
         Self { 
-            code: 404,  
+            code: StatusCode::NOT_FOUND,  
             body: Default::default(), 
-            headers: Default::default() }
+            headers: Default::default(),
+            close_delimited: false }
     }
 }
 
 
 
-fn construct_status_line(code : i32) -> String {
-    format!("{} {} {}", PROTOCOL, code, message_from_code(code))
+fn has_no_content(code: StatusCode) -> bool {
+    code.as_u16() < 200 || code == StatusCode::NO_CONTENT || code == StatusCode::NOT_MODIFIED
 }
 
-fn message_from_code(code : i32) -> String {
-    match code {
-        200 =>  String::from("OK"),
-        500 => "INTERNAL".to_string(),
-        404 => "NOT FOUND".to_string(),
-        400 => "BAD REQUEST".to_string(),
-        _  => "".to_string()
-    }
+fn construct_status_line(code : StatusCode) -> String {
+    format!("{} {} {}", PROTOCOL, code, code.reason_phrase())
 }
 
 impl fmt::Display for HTTPResponse {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let headers= self.headers.iter().map(|(key, value)| key.to_string() + ": " + value).collect::<Vec<String>>().join("\r\n");
-        let body ="\r\n".to_string() +  &self.body.to_owned().unwrap_or_default();
-        write!(f,
-            "{}\r\n{}{}",
-            construct_status_line(self.code),
-            headers,
-            body
-        )
+        let body = match &self.body {
+            Some(Body::Bytes(bytes)) if !has_no_content(self.code) => String::from_utf8_lossy(bytes),
+            _ => "".into()
+        };
+        write!(f, "{}{}", self.head(), body)
     }
 }
 
@@ -59,10 +135,18 @@ impl fmt::Display for HTTPResponse {
 impl From<HttpError> for HTTPResponse {
     fn from(value: HttpError) -> Self {
         match value {
-            HttpError::DefaultError => ResponseBuilder::new(0, None).build(),
-            HttpError::NotFoundError(_) => ResponseBuilder::new(404, Some("Not found".to_string())).build(),
-            HttpError::UnauthorizedError(_) => ResponseBuilder::new(401, None).build(),
-            HttpError::BadRequest(_) => ResponseBuilder::new(400, None).build(),
+            HttpError::DefaultError => ResponseBuilder::new(StatusCode::INTERNAL_SERVER_ERROR, None).build(),
+            HttpError::NotFoundError(_) => ResponseBuilder::new(StatusCode::NOT_FOUND, Some("Not found".to_string())).build(),
+            HttpError::UnauthorizedError(_) => ResponseBuilder::new(StatusCode::UNAUTHORIZED, None).build(),
+            HttpError::AuthenticationRequired(challenge, _) => ResponseBuilder::new(StatusCode::UNAUTHORIZED, None)
+                .put_header("WWW-Authenticate".to_string(), challenge)
+                .build(),
+            HttpError::Forbidden(_) => ResponseBuilder::new(StatusCode::FORBIDDEN, None).build(),
+            HttpError::BadRequest(_) => ResponseBuilder::new(StatusCode::BAD_REQUEST, None).build(),
+            HttpError::PayloadTooLarge(_) => ResponseBuilder::new(StatusCode::CONTENT_TOO_LARGE, None).build(),
+            HttpError::MethodNotAllowed(verbs) => ResponseBuilder::new(StatusCode::METHOD_NOT_ALLOWED, None)
+                .put_header("Allow".to_string(), verbs.iter().map(Verb::to_string).collect::<Vec<String>>().join(", "))
+                .build(),
         }
     }
 }
@@ -70,32 +154,33 @@ impl From<HttpError> for HTTPResponse {
 
 #[derive(Default)]
 pub struct ResponseBuilder {
-    code: i32,
-    body: Option<String>,
+    code: StatusCode,
+    body: Option<Body>,
     headers: Headers
 }
 
 
 impl ResponseBuilder {
-    pub fn new(code: i32, body: Option<String>) -> Self {
+    pub fn new(code: StatusCode, body: Option<String>) -> Self {
         ResponseBuilder {
             code,
-            body,
+            body: body.map(String::into_bytes).map(Body::Bytes),
             ..Default::default()
         }
     }
 
     pub fn content_type(mut self, content_type: String) -> Self {
+        self.headers.retain(|(header, _)| !header.eq_ignore_ascii_case("Content-Type"));
         self.headers.push(("Content-Type".to_string(), content_type)); 
         self
     }
 
-    pub fn build(&self) -> HTTPResponse{
-        let headers = vec![];
+    pub fn build(self) -> HTTPResponse{
         HTTPResponse {
-            body: self.body.clone(),
+            body: self.body,
             code: self.code,
-            headers
+            headers: self.headers,
+            close_delimited: false
         }   
     }
 
@@ -104,8 +189,131 @@ impl ResponseBuilder {
         self
     }
 
+    /// Adds a `Set-Cookie` header, one per cookie.
+    pub fn cookie(mut self, cookie: &Cookie) -> ResponseBuilder {
+        self.headers.push(("Set-Cookie".to_string(), cookie.to_string()));
+        self
+    }
+
     pub fn body(mut self, body: String) -> ResponseBuilder {
-        self.body = Some(body);
+        self.body = Some(Body::Bytes(body.into_bytes()));
         self
     }
+
+    pub fn bytes(mut self, body: Vec<u8>) -> ResponseBuilder {
+        self.body = Some(Body::Bytes(body));
+        self
+    }
+
+    /// Streams the body from a reader, without buffering it in memory.
+    pub fn stream<R: Read + Send + 'static>(mut self, reader: R) -> ResponseBuilder {
+        self.body = Some(Body::Reader(Box::new(reader)));
+        self
+    }
+
+    /// Streams the body as a sequence of chunks, each item being sent as soon as it is produced.
+    pub fn chunks<I, C>(mut self, chunks: I) -> ResponseBuilder
+    where
+        I: Iterator<Item = C> + Send + 'static,
+        C: Into<Vec<u8>> + 'static
+    {
+        self.body = Some(Body::Chunks(Box::new(chunks.map(Into::into))));
+        self
+    }
+}
+
+
+// UNIT TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_keeps_headers_and_adds_framing() {
+        let response = ResponseBuilder::new(StatusCode::OK, Some("toto".to_string()))
+            .content_type("text/plain".to_string())
+            .put_header("X-Request-Id".to_string(), "42".to_string())
+            .build()
+            .to_string();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nX-Request-Id: 42\r\nContent-Length: 4\r\nDate: "));
+        assert!(response.ends_with("GMT\r\n\r\ntoto"));
+    }
+
+    #[test]
+    fn handler_headers_override_defaults_but_not_the_length() {
+        let empty = ResponseBuilder::new(StatusCode::OK, None)
+            .put_header("Date".to_string(), "Thu, 01 Jan 1970 00:00:00 GMT".to_string())
+            .put_header("Content-Length".to_string(), "12".to_string())
+            .build()
+            .to_string();
+        let toto = ResponseBuilder::new(StatusCode::OK, Some("toto".to_string()))
+            .put_header("Content-Length".to_string(), "12".to_string())
+            .build();
+
+        assert_eq!(empty, "HTTP/1.1 200 OK\r\nDate: Thu, 01 Jan 1970 00:00:00 GMT\r\nContent-Length: 0\r\n\r\n");
+        assert!(toto.to_string().contains("Content-Length: 4\r\n"));
+        assert!(!toto.to_string().contains("Content-Length: 12"));
+    }
+
+    #[test]
+    fn responses_without_content_have_no_framing_headers() {
+        let no_content = ResponseBuilder::new(StatusCode::NO_CONTENT, None)
+            .put_header("Date".to_string(), "Thu, 01 Jan 1970 00:00:00 GMT".to_string())
+            .put_header("Content-Length".to_string(), "0".to_string())
+            .build()
+            .to_string();
+        let switching = ResponseBuilder::new(StatusCode::SWITCHING_PROTOCOLS, None)
+            .put_header("Date".to_string(), "Thu, 01 Jan 1970 00:00:00 GMT".to_string())
+            .chunks(["to", "to"].into_iter())
+            .build()
+            .to_string();
+
+        assert_eq!(no_content, "HTTP/1.1 204 No Content\r\nDate: Thu, 01 Jan 1970 00:00:00 GMT\r\n\r\n");
+        assert_eq!(switching, "HTTP/1.1 101 Switching Protocols\r\nDate: Thu, 01 Jan 1970 00:00:00 GMT\r\n\r\n");
+    }
+
+    #[test]
+    fn not_modified_keeps_the_handler_length() {
+        let mut output = vec![];
+        ResponseBuilder::new(StatusCode::NOT_MODIFIED, Some("toto".to_string()))
+            .put_header("Content-Length".to_string(), "12".to_string())
+            .put_header("Date".to_string(), "Thu, 01 Jan 1970 00:00:00 GMT".to_string())
+            .build()
+            .write_to(&mut output)
+            .unwrap();
+        let without_length = ResponseBuilder::new(StatusCode::NOT_MODIFIED, None).build().to_string();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "HTTP/1.1 304 Not Modified\r\nContent-Length: 12\r\nDate: Thu, 01 Jan 1970 00:00:00 GMT\r\n\r\n");
+        assert!(!without_length.contains("Content-Length"));
+    }
+
+    #[test]
+    fn cookies_are_sent_in_separate_headers() {
+        let response = ResponseBuilder::new(StatusCode::OK, None)
+            .cookie(&Cookie::new("session", "42").unwrap().http_only())
+            .cookie(&Cookie::new("theme", "dark").unwrap())
+            .build();
+
+        let cookies = response.headers().into_iter()
+            .filter(|(key, _)| key == "Set-Cookie")
+            .map(|(_, value)| value)
+            .collect::<Vec<String>>();
+        assert_eq!(cookies, vec!["session=42; HttpOnly".to_string(), "theme=dark".to_string()]);
+    }
+
+    #[test]
+    fn stream_with_content_length_is_not_chunked() {
+        let mut output = vec![];
+
+        ResponseBuilder::new(StatusCode::OK, None)
+            .put_header("Content-Length".to_string(), "4".to_string())
+            .put_header("Date".to_string(), "Thu, 01 Jan 1970 00:00:00 GMT".to_string())
+            .stream("toto".as_bytes())
+            .build()
+            .write_to(&mut output)
+            .unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "HTTP/1.1 200 OK\r\nContent-Length: 4\r\nDate: Thu, 01 Jan 1970 00:00:00 GMT\r\n\r\ntoto");
+    }
 }
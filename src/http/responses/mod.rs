@@ -1,4 +1,7 @@
+mod body;
 mod http_response;
+mod status_code;
 
 pub use http_response::HTTPResponse;
 pub use http_response::ResponseBuilder;
+pub use status_code::{InvalidStatusCode, StatusCode};
@@ -1,8 +1,14 @@
 mod structs;
+#[allow(clippy::module_inception)]
 mod router;
+mod tree;
 
 
 pub use router::handle_request;
+pub use tree::RouteTree;
 pub use structs::Routes;
 pub use structs::Route;
-pub use structs::RequestHandler;
\ No newline at end of file
+pub use structs::Router;
+pub use structs::RequestHandler;
+pub use structs::{Middleware, Middlewares};
+pub use structs::{default_error_mapper, ErrorMapper, Handler, HandlerError, IntoHandlerResult};
\ No newline at end of file
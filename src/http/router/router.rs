@@ -1,138 +1,334 @@
-use std::panic::catch_unwind;
+use std::panic::{catch_unwind, AssertUnwindSafe};
 
 use log::info;
 
-use crate::{http::{ errors::{http_errors::HttpError, InternalError},  security::service::{apply_security, SecurityProtocol}}, Config, HTTPRequest, HTTPResponse, ResponseBuilder, Route, Verb};
+use crate::{http::{ errors::{http_errors::HttpError, InternalError},  security::{principal::Principal, service::apply_security}}, Config, Cors, HTTPRequest, HTTPResponse, ResponseBuilder, Route, State, StatusCode, Verb};
 
-use super::{ structs::RequestHandler, Routes};
+use super::{ structs::{ErrorMapper, Middlewares, RequestHandler}, tree::RouteTree};
 
-pub fn handle_request(request: &HTTPRequest, handler : Routes, config: Config) -> HTTPResponse {
-    let response = catch_unwind(||route(request, handler, config.security()))
+pub fn handle_request(request: &HTTPRequest, handler : &RouteTree, config: Config, state: State, error_mapper: &ErrorMapper, middlewares: &Middlewares) -> HTTPResponse {
+    let mut incoming = HTTPRequest { principal: None, ..request.clone() };
+    let response = catch_unwind(AssertUnwindSafe(|| 
+            chain(middlewares, &mut incoming, |request| route(request, handler, &config, state, error_mapper))))
         .map_err(|_| InternalError::from("Internal Server Error"))
-        .map(HTTPResponse::from)
         .unwrap_or_else(HTTPResponse::from);
+    let response = with_cors(request, response, config.cors());
 
-    access_log(request, &response);
+    access_log(request, &response, incoming.principal);
     
     response
 }
 
-fn access_log(request: &HTTPRequest, response: &HTTPResponse) {
-    info!("{} {} {}", request.verb, request.resource, response.code())
+fn access_log(request: &HTTPRequest, response: &HTTPResponse, principal: Option<Principal>) {
+    match principal {
+        Some(principal) => info!("{} {} {} {}", request.verb, request.resource, response.code(), principal.username),
+        None => info!("{} {} {}", request.verb, request.resource, response.code())
+    }
 }
 
-fn route(request: &HTTPRequest, handler : Routes, security: SecurityProtocol) -> HTTPResponse {
-    match request.verb {
-        Verb::OPTION => options(),
-        _ => {
-            find_route(request, &handler)
-                .and_then(|route| apply_security(request, route, security))
-                .map(|route| execute(request, route))
-                .unwrap_or_else(HTTPResponse::from)
-            }
+/// Runs `next` wrapped by the middlewares: `before` in order, then `after` in reverse order
+/// for every middleware whose `before` ran, the one that short-circuited included.
+fn chain<F>(middlewares: &Middlewares, request: &mut HTTPRequest, next: F) -> HTTPResponse
+where
+    F: FnOnce(&mut HTTPRequest) -> HTTPResponse
+{
+    let mut ran = 0;
+    let mut short_circuit = None;
+    for middleware in middlewares {
+        ran += 1;
+        short_circuit = middleware.before(request);
+        if short_circuit.is_some() {
+            break;
+        }
     }
-}
 
-fn execute(request: &HTTPRequest, route: Route ) -> HTTPResponse {
-    let handler = RequestHandler::from((request, &route));
-    (route.method)(&handler)   
+    let response = short_circuit.unwrap_or_else(|| next(request));
+    middlewares[..ran].iter()
+        .rev()
+        .fold(response, |response, middleware| middleware.after(request, response))
 }
 
+fn route(request: &mut HTTPRequest, handler : &RouteTree, config: &Config, state: State, error_mapper: &ErrorMapper) -> HTTPResponse {
+    match find_route(request, handler) {
+        Ok(route) => {
+            let middlewares = route.middlewares.clone();
+            chain(&middlewares, request, |request| secure_and_execute(request, route, config, state, error_mapper))
+        },
+        Err(HttpError::MethodNotAllowed(verbs)) if request.verb == Verb::OPTION => options(request, verbs, config.cors()),
+        Err(error) => error_mapper(&error.into())
+    }
+}
 
-fn find_route(request: &HTTPRequest, handler : &Routes) -> Result<Route, HttpError> {
-     handler.iter()
-                .filter(|route| route.verb == request.verb)
-                .find(|&route| 
-                    valid_against(request.resource.clone(), route.route.clone()))
-                .map(|r| r.to_owned())
-                .ok_or(HttpError::NotFoundError("Coult not find ressource".to_string()))
+fn secure_and_execute(request: &mut HTTPRequest, route: Route, config: &Config, state: State, error_mapper: &ErrorMapper) -> HTTPResponse {
+    match apply_security(request, &route, config) {
+        Ok(principal) => {
+            request.principal = principal;
+            execute(request, route, state, error_mapper)
+        },
+        Err(error) => error_mapper(&error.into())
+    }
 }
 
-fn options() -> HTTPResponse {
-        ResponseBuilder::new(200, None)
-            .put_header("Access-Control-Allow-Methods".to_string(), "POST, GET, DELETE, PATCH, OPTIONS".to_string())
-            .build()
+fn execute(request: &HTTPRequest, route: Route, state: State, error_mapper: &ErrorMapper) -> HTTPResponse {
+    let handler = RequestHandler::from((request, &route)).with_state(state);
+    route.method.handle(&handler)
+        .unwrap_or_else(|error| error_mapper(&error))
 }
 
-fn valid_against(request: String,reference: String) -> bool {    
-    let splitted_reference = reference.split('/').collect::<Vec<&str>>();
-    let request_iter = request.split('/').collect::<Vec<&str>>();
 
-    let mut reference_iter = splitted_reference.iter();
+fn find_route(request: &HTTPRequest, handler : &RouteTree) -> Result<Route, HttpError> {
+    handler.find(&request.verb, &request.resource)
+        .cloned()
+        .ok_or_else(|| match handler.allowed_verbs(&request.resource) {
+            verbs if verbs.is_empty() => HttpError::NotFoundError("Coult not find ressource".to_string()),
+            verbs => HttpError::MethodNotAllowed(verbs)
+        })
+}
+
+/// Automatic answer to an OPTIONS request on a path without an explicit OPTIONS route,
+/// computed from the verbs registered for that path.
+fn options(request: &HTTPRequest, mut verbs: Vec<Verb>, cors: Option<Cors>) -> HTTPResponse {
+    verbs.push(Verb::OPTION);
+    let mut response = ResponseBuilder::new(StatusCode::NO_CONTENT, None)
+        .put_header("Allow".to_string(), verbs.iter().map(Verb::to_string).collect::<Vec<String>>().join(", "))
+        .build();
 
-    return (splitted_reference.len() == request_iter.len()) && 
-    request_iter.iter()
-        .all(|request_part| compare(request_part, reference_iter.next()));
+    let is_preflight = request.get_header("Access-Control-Request-Method").is_some();
+    if let (Some(cors), Some((_, origin)), true) = (cors, request.get_header("Origin"), is_preflight) {
+        cors.preflight_headers(&origin, &verbs)
+            .iter()
+            .for_each(|(key, value)| response.put_header(key, value));
+    }
+    response
 }
 
-fn compare(req_part: &str, ref_part: Option<&&str>)  -> bool {
-    match ref_part {
-        None => false,
-        Some(&ref_part) => {
-            ref_part.starts_with('{') || req_part == ref_part
-        }
+fn with_cors(request: &HTTPRequest, mut response: HTTPResponse, cors: Option<Cors>) -> HTTPResponse {
+    if let (Some(cors), Some((_, origin))) = (cors, request.get_header("Origin")) {
+        cors.response_headers(&origin)
+            .iter()
+            .for_each(|(key, value)| match key.eq_ignore_ascii_case("Vary") {
+                true => response.add_to_header(key, value),
+                false => response.put_default_header(key, value)
+            });
     }
+    response
 }
 
 
 // UNIT TEST
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
     use super::*;
+    use crate::http::router::structs::{default_error_mapper, Middleware};
+    use crate::http::security::service::SecurityProtocol;
 
     #[test]
-    fn compare_ressource_ok() {
-        let reference ="route";
-        let incoming ="route";
-        let result = compare(incoming, Some(&reference));
-        assert!(result);
+    fn handle_request_without_security_should_return_200() {
+
+        let request = HTTPRequest::default();
+        let routes = RouteTree::from(&vec![Route::default()]);
+        let config = Config::default();
+
+        let responses_code = handle_request(&request, &routes, config, State::default(), &error_mapper(), &vec![]).to_string().split("\r\n").map(|s| s.to_string()).collect::<Vec<String>>();
+        assert_eq!(responses_code.first().unwrap(), &"HTTP/1.1 200 OK");
     }
 
     #[test]
-    fn compare_ressource_nok() {
-        let reference: &str ="route";
-        let incoming ="routes";
-        let result = compare(incoming, Some(&reference));
-        assert!(!result);
+    fn handle_request_with_closure_and_state() {
+        let greeting = "hello".to_string();
+        let route = Route::new(&Verb::GET, "/", move |handler: &RequestHandler| {
+            let name = handler.state::<String>().unwrap();
+            ResponseBuilder::new(StatusCode::OK, Some(format!("{} {}", greeting, name))).build()
+        }, false);
+        let mut state = State::default();
+        state.insert("toto".to_string());
+
+        let response = handle_request(&HTTPRequest::default(), &RouteTree::from(&vec![route]), Config::default(), state, &error_mapper(), &vec![]).to_string();
+
+        assert!(response.ends_with("\r\n\r\nhello toto"));
     }
 
     #[test]
-    fn compare_param_ok() {
-        let reference ="{id}";
-        let incoming ="2";
-        let result = compare(incoming, Some(&reference));
-        assert!(result);
+    fn handle_request_maps_handler_errors() {
+        let not_found = Route::new(&Verb::GET, "/", |_: &RequestHandler| -> Result<HTTPResponse, HttpError> {
+            Err(HttpError::NotFoundError("toto".to_string()))
+        }, false);
+        let failing = Route::new(&Verb::GET, "/", |_: &RequestHandler| -> Result<HTTPResponse, std::io::Error> {
+            Err(std::io::Error::other("disk full"))
+        }, false);
+        let teapot: ErrorMapper = Arc::new(|_| ResponseBuilder::new(StatusCode::from_u16(418).unwrap(), None).build());
+
+        let default = |route| handle_request(&HTTPRequest::default(), &RouteTree::from(&vec![route]), Config::default(), State::default(), &error_mapper(), &vec![]).code();
+
+        assert_eq!(default(not_found.clone()), StatusCode::NOT_FOUND);
+        assert_eq!(default(failing), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(handle_request(&HTTPRequest::default(), &RouteTree::from(&vec![not_found]), Config::default(), State::default(), &teapot, &vec![]).code().as_u16(), 418);
     }
 
     #[test]
-    fn test_valid_against_combinations() {
-        let combinations :Vec<(Combination, ExpectedResult)> = vec![
-            (Combination::new("ressource/1/toto/2", "ressource/id/toto/{dd}"), false),
-            (Combination::new("ressource/1/toto/2", "ressource/{id}/toto/{dd}"), true),
-            (Combination::new("ressource/1/toto/2?test=12", "ressource/{id}/toto/{dd}"), true)
-            ];
-            
-        combinations.iter().for_each(|combinaisons| assert_eq!(valid_against(combinaisons.0.input.clone(), combinaisons.0.template.clone()), combinaisons.1));
-
-    }
-
-    struct Combination{input: String,template:  String}
-    type ExpectedResult = bool;
-    impl Combination {
-        pub fn new(input: &str, template: &str) -> Combination {
-            Combination{input: input.to_string(),template: template.to_string()}
+    fn handle_request_maps_framework_errors() {
+        let secured = Route { route: "/secured".to_string(), need_security: true, ..Route::default() };
+        let routes = RouteTree::from(&vec![secured]);
+        let config = Config::default().with_security(&SecurityProtocol::basic(|_| false)).to_owned();
+        let json: ErrorMapper = Arc::new(|error| match error.downcast_ref::<HttpError>() {
+            Some(HttpError::NotFoundError(_)) => ResponseBuilder::new(StatusCode::NOT_FOUND, Some("{\"error\":\"not found\"}".to_string())).build(),
+            Some(HttpError::AuthenticationRequired(_, _)) => ResponseBuilder::new(StatusCode::UNAUTHORIZED, Some("{\"error\":\"unauthorized\"}".to_string())).build(),
+            _ => ResponseBuilder::new(StatusCode::INTERNAL_SERVER_ERROR, None).build()
+        });
+        let unknown = HTTPRequest { resource: "/unknown".to_string(), ..HTTPRequest::default() };
+        let anonymous = HTTPRequest { resource: "/secured".to_string(), ..HTTPRequest::default() };
+
+        let not_found = handle_request(&unknown, &routes, config.clone(), State::default(), &json, &vec![]).to_string();
+        let unauthorized = handle_request(&anonymous, &routes, config, State::default(), &json, &vec![]).to_string();
+
+        assert!(not_found.ends_with("{\"error\":\"not found\"}"));
+        assert!(unauthorized.ends_with("{\"error\":\"unauthorized\"}"));
+    }
+
+    struct Tag(&'static str, bool);
+
+    impl Middleware for Tag {
+        fn before(&self, request: &mut HTTPRequest) -> Option<HTTPResponse> {
+            request.resource = format!("{}/{}", request.resource.trim_end_matches('/'), self.0);
+            self.1.then(|| ResponseBuilder::new(StatusCode::FORBIDDEN, None).build())
+        }
+
+        fn after(&self, _: &HTTPRequest, mut response: HTTPResponse) -> HTTPResponse {
+            let trace = response.get_header("X-Trace").unwrap_or_default() + self.0;
+            response.put_header("X-Trace", &trace);
+            response
         }
     }
 
+    #[test]
+    fn handle_request_runs_global_then_route_middlewares() {
+        let route = Route::new(&Verb::GET, "/a", |_: &RequestHandler| 
+            ResponseBuilder::new(StatusCode::OK, None).put_header("X-Trace".to_string(), "h".to_string()).build(), false)
+            .with_middleware(Tag("b", false));
+        let middlewares: Middlewares = vec![Arc::new(Tag("a", false))];
+
+        let response = handle_request(&HTTPRequest::default(), &RouteTree::from(&vec![route]), Config::default(), State::default(), &error_mapper(), &middlewares);
+
+        assert_eq!(response.code(), StatusCode::OK);
+        assert_eq!(response.get_header("X-Trace"), Some("hba".to_string()));
+    }
 
     #[test]
-    fn handle_request_without_security_should_return_200() {
+    fn handle_request_short_circuits_in_middleware() {
+        let middlewares: Middlewares = vec![Arc::new(Tag("a", true)), Arc::new(Tag("b", false))];
 
-        let request = HTTPRequest::default();
-        let routes = vec![Route::default()];
-        let config = Config::default();
+        let response = handle_request(&HTTPRequest::default(), &RouteTree::from(&vec![Route::default()]), Config::default(), State::default(), &error_mapper(), &middlewares);
 
-        let responses_code = handle_request(&request, routes, config).to_string().split("\r\n").map(|s| s.to_string()).collect::<Vec<String>>();
-        assert_eq!(responses_code.first().unwrap(), &"HTTP/1.1 200 OK");
+        assert_eq!(response.code(), StatusCode::FORBIDDEN);
+        assert_eq!(response.get_header("X-Trace"), Some("a".to_string()));
+    }
+
+    #[test]
+    fn handle_request_on_other_verb_is_method_not_allowed() {
+        let routes = RouteTree::from(&vec![Route::default(), Route { verb: Verb::DELETE, ..Route::default() }]);
+        let request = HTTPRequest { verb: Verb::PUT, ..HTTPRequest::default() };
+
+        let response = handle_request(&request, &routes, Config::default(), State::default(), &error_mapper(), &vec![]);
+
+        assert_eq!(response.code(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.get_header("Allow"), Some("GET, DELETE".to_string()));
+    }
+
+    #[test]
+    fn handle_request_answers_options_from_registered_verbs() {
+        let routes = RouteTree::from(&vec![Route::default(), Route { verb: Verb::POST, ..Route::default() }]);
+        let config = Config::default()
+            .with_cors(Cors::initialize().with_allowed_origins(&["https://ilmen.fr"]).with_max_age(&600))
+            .to_owned();
+        let preflight = HTTPRequest::try_from("OPTIONS / HTTP/1.1\r\nOrigin: https://ilmen.fr\r\nAccess-Control-Request-Method: POST\r\n\r\n").unwrap();
+        let unknown = HTTPRequest::try_from("OPTIONS /unknown HTTP/1.1\r\n\r\n").unwrap();
+
+        let response = handle_request(&preflight, &routes, config.clone(), State::default(), &error_mapper(), &vec![]);
+        let not_found = handle_request(&unknown, &routes, config, State::default(), &error_mapper(), &vec![]);
+
+        assert_eq!(response.code(), StatusCode::NO_CONTENT);
+        assert_eq!(response.get_header("Allow"), Some("GET, POST, OPTIONS".to_string()));
+        assert_eq!(response.get_header("Access-Control-Allow-Methods"), Some("GET, POST, OPTIONS".to_string()));
+        assert_eq!(response.get_header("Access-Control-Allow-Origin"), Some("https://ilmen.fr".to_string()));
+        assert_eq!(response.get_header("Access-Control-Max-Age"), Some("600".to_string()));
+        assert!(!response.to_string().contains("Content-Length"));
+        assert_eq!(not_found.code(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn handle_request_applies_cors_to_actual_responses() {
+        let routes = RouteTree::from(&vec![Route::default()]);
+        let config = Config::default()
+            .with_cors(Cors::initialize().with_allowed_origins(&["*"]))
+            .to_owned();
+        let request = HTTPRequest::try_from("GET / HTTP/1.1\r\nOrigin: https://ilmen.fr\r\n\r\n").unwrap();
+
+        let response = handle_request(&request, &routes, config, State::default(), &error_mapper(), &vec![]);
+
+        assert_eq!(response.code(), StatusCode::OK);
+        assert_eq!(response.get_header("Access-Control-Allow-Origin"), Some("*".to_string()));
+    }
+
+    #[test]
+    fn handle_request_adds_origin_to_the_handler_vary() {
+        let route = Route::new(&Verb::GET, "/", |_: &RequestHandler|
+            ResponseBuilder::new(StatusCode::OK, None).put_header("Vary".to_string(), "Accept-Encoding".to_string()).build(), false);
+        let config = Config::default()
+            .with_cors(Cors::initialize().with_allowed_origins(&["https://ilmen.fr"]))
+            .to_owned();
+        let request = HTTPRequest::try_from("GET / HTTP/1.1\r\nOrigin: https://ilmen.fr\r\n\r\n").unwrap();
+
+        let response = handle_request(&request, &RouteTree::from(&vec![route]), config, State::default(), &error_mapper(), &vec![]);
+
+        assert_eq!(response.get_header("Vary"), Some("Accept-Encoding, Origin".to_string()));
+    }
+
+    #[test]
+    fn handle_request_exposes_token_claims_to_handlers() {
+        let route = Route::new(&Verb::GET, "/", |handler: &RequestHandler| {
+            let role = handler.claims().and_then(|claims| claims.get("role").cloned()).unwrap_or_default();
+            ResponseBuilder::new(StatusCode::OK, Some(role.to_string())).build()
+        }, true);
+        let config = Config::default()
+            .with_security(&SecurityProtocol::bearer(|token| {
+                let mut principal = Principal::new(token);
+                principal.claims.insert("role".to_string(), "admin".into());
+                Ok(principal)
+            }))
+            .to_owned();
+        let request = HTTPRequest::try_from("GET / HTTP/1.1\r\nAuthorization: Bearer toto\r\n\r\n").unwrap();
+
+        let response = handle_request(&request, &RouteTree::from(&vec![route]), config, State::default(), &error_mapper(), &vec![]).to_string();
+
+        assert!(response.ends_with("\r\n\r\n\"admin\""));
+    }
+
+    #[test]
+    fn handle_request_only_trusts_the_principal_set_by_security() {
+        let route = Route::new(&Verb::GET, "/", |handler: &RequestHandler|
+            ResponseBuilder::new(StatusCode::OK, handler.principal().map(|principal| principal.username)).build(), false);
+        let request = HTTPRequest { principal: Some(Principal::new("forged")), ..HTTPRequest::default() };
+
+        let response = handle_request(&request, &RouteTree::from(&vec![route]), Config::default(), State::default(), &error_mapper(), &vec![]).to_string();
+
+        assert!(response.ends_with("\r\n\r\n"));
+    }
+
+    #[test]
+    fn handle_request_exposes_basic_principal_to_handlers() {
+        let route = Route::new(&Verb::GET, "/", |handler: &RequestHandler|
+            ResponseBuilder::new(StatusCode::OK, handler.principal().map(|principal| principal.username)).build(), true);
+        let config = Config::default().with_security(&SecurityProtocol::basic(|_| true)).to_owned();
+        let request = HTTPRequest::try_from("GET / HTTP/1.1\r\nAuthorization: Basic dG90bzp0YXRh\r\n\r\n").unwrap();
+
+        let response = handle_request(&request, &RouteTree::from(&vec![route]), config, State::default(), &error_mapper(), &vec![]).to_string();
+
+        assert!(response.ends_with("\r\n\r\ntoto"));
+    }
+
+    fn error_mapper() -> ErrorMapper {
+        Arc::new(default_error_mapper)
     }
 }
\ No newline at end of file
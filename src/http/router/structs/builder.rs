@@ -0,0 +1,194 @@
+use std::sync::Arc;
+
+use super::middleware::{Middleware, Middlewares};
+use super::route::{Route, Routes};
+
+/// Assembles a route table from routes declared in different places.
+///
+/// A router can be nested under a path prefix or merged into another one. Middlewares and
+/// security attached to a router apply to all of its routes, nested ones included, and run
+/// outside the middlewares attached to the routes themselves.
+#[derive(Clone, Default)]
+pub struct Router {
+    routes: Routes,
+    middlewares: Middlewares,
+    need_security: bool,
+    security: Vec<String>,
+    roles: Vec<String>,
+    scopes: Vec<String>
+}
+
+impl Router {
+    pub fn new() -> Router {
+        Router::default()
+    }
+
+    pub fn route(mut self, route: Route) -> Router {
+        self.routes.push(route);
+        self
+    }
+
+    pub fn routes(mut self, routes: Routes) -> Router {
+        self.routes.extend(routes);
+        self
+    }
+
+    /// Adds the routes of another router, with its own middlewares and security applied.
+    pub fn merge(self, other: Router) -> Router {
+        self.routes(other.build())
+    }
+
+    /// Adds the routes of another router under the path prefix.
+    pub fn nest(self, prefix: &str, other: Router) -> Router {
+        let routes = other.build()
+            .into_iter()
+            .map(|route| Route { route: join(prefix, &route.route), ..route })
+            .collect();
+        self.routes(routes)
+    }
+
+    pub fn with_middleware<M: Middleware + 'static>(mut self, middleware: M) -> Router {
+        self.middlewares.push(Arc::new(middleware));
+        self
+    }
+
+    /// Requires the server security on every route of this router.
+    pub fn with_security(mut self) -> Router {
+        self.need_security = true;
+        self
+    }
+
+    /// Requires the named scheme on every route of this router not naming its own schemes.
+    pub fn with_security_scheme(mut self, scheme: &str) -> Router {
+        self.need_security = true;
+        self.security.push(scheme.to_string());
+        self
+    }
+
+    /// Requires one of these roles on every route of this router not requiring its own roles.
+    pub fn with_roles(mut self, roles: &[&str]) -> Router {
+        self.need_security = true;
+        self.roles = roles.iter().map(|role| role.to_string()).collect();
+        self
+    }
+
+    /// Requires all of these scopes on every route of this router not requiring its own scopes.
+    pub fn with_scopes(mut self, scopes: &[&str]) -> Router {
+        self.need_security = true;
+        self.scopes = scopes.iter().map(|scope| scope.to_string()).collect();
+        self
+    }
+
+    pub fn build(self) -> Routes {
+        self.routes.into_iter()
+            .map(|route| Route {
+                need_security: route.need_security || self.need_security,
+                security: match route.security.is_empty() {
+                    true => self.security.clone(),
+                    false => route.security
+                },
+                roles: match route.roles.is_empty() {
+                    true => self.roles.clone(),
+                    false => route.roles
+                },
+                scopes: match route.scopes.is_empty() {
+                    true => self.scopes.clone(),
+                    false => route.scopes
+                },
+                middlewares: self.middlewares.iter().cloned().chain(route.middlewares).collect(),
+                ..route
+            })
+            .collect()
+    }
+}
+
+impl From<Router> for Routes {
+    fn from(router: Router) -> Self {
+        router.build()
+    }
+}
+
+fn join(prefix: &str, route: &str) -> String {
+    let prefix = prefix.trim_end_matches('/');
+    match route.trim_start_matches('/') {
+        "" if prefix.is_empty() => "/".to_string(),
+        "" => prefix.to_string(),
+        route => format!("{}/{}", prefix, route)
+    }
+}
+
+
+// UNIT TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Noop;
+    impl Middleware for Noop {}
+
+    fn route(path: &str) -> Route {
+        Route { route: path.to_string(), ..Route::default() }
+    }
+
+    #[test]
+    fn nest_prefixes_paths() {
+        let books = Router::new().route(route("/")).route(route("/books/{id}"));
+        let routes = Router::new().nest("/api/v1/", books).build();
+
+        assert_eq!(routes.iter().map(|route| route.route.as_str()).collect::<Vec<&str>>(), vec!["/api/v1", "/api/v1/books/{id}"]);
+    }
+
+    #[test]
+    fn scope_security_and_middlewares_apply_to_nested_routes() {
+        let admin = Router::new()
+            .route(route("/users").with_middleware(Noop))
+            .with_middleware(Noop)
+            .with_security();
+        let routes = Router::new()
+            .route(route("/health"))
+            .nest("/admin", admin)
+            .with_middleware(Noop)
+            .build();
+
+        let health = &routes[0];
+        let users = &routes[1];
+        assert!(!health.need_security);
+        assert_eq!(health.middlewares.len(), 1);
+        assert!(users.need_security);
+        assert_eq!(users.middlewares.len(), 3);
+    }
+
+    #[test]
+    fn scope_roles_apply_to_routes_without_their_own() {
+        let routes = Router::new()
+            .route(route("/users"))
+            .route(route("/audit").with_roles(&["auditor"]))
+            .with_roles(&["admin"])
+            .with_scopes(&["admin:read"])
+            .build();
+
+        assert!(routes[0].need_security);
+        assert_eq!(routes[0].roles, vec!["admin".to_string()]);
+        assert_eq!(routes[1].roles, vec!["auditor".to_string()]);
+        assert_eq!(routes[1].scopes, vec!["admin:read".to_string()]);
+    }
+
+    #[test]
+    fn scope_scheme_does_not_override_route_schemes() {
+        let routes = Router::new()
+            .route(route("/users"))
+            .route(route("/keys").with_security_scheme("api"))
+            .with_security_scheme("admin")
+            .build();
+
+        assert_eq!(routes[0].security, vec!["admin".to_string()]);
+        assert_eq!(routes[1].security, vec!["api".to_string()]);
+    }
+
+    #[test]
+    fn merge_keeps_both_tables() {
+        let routes = Router::new().route(route("/a")).merge(Router::new().route(route("/b"))).build();
+
+        assert_eq!(routes, vec![route("/a"), route("/b")]);
+    }
+}
@@ -0,0 +1,95 @@
+use std::str::FromStr;
+
+use regex::Regex;
+
+/// Restriction on the values a `{name:constraint}` path parameter accepts.
+///
+/// The constraint is either a type name (`u64`, `i32`, `f64`, `bool`, `uuid`...) or a regular
+/// expression that must match the whole segment, such as `[a-z-]+`.
+#[derive(Clone, Debug)]
+pub struct Constraint {
+    source: String,
+    matcher: Matcher
+}
+
+#[derive(Clone, Debug)]
+enum Matcher {
+    Parse(fn(&str) -> bool),
+    Pattern(Regex)
+}
+
+impl Constraint {
+    /// # Panics
+    ///
+    /// Panics if the constraint is neither a known type nor a valid regular expression.
+    pub fn new(source: &str) -> Constraint {
+        let matcher = match source {
+            "u8" => Matcher::Parse(parses::<u8>),
+            "u16" => Matcher::Parse(parses::<u16>),
+            "u32" => Matcher::Parse(parses::<u32>),
+            "u64" => Matcher::Parse(parses::<u64>),
+            "u128" => Matcher::Parse(parses::<u128>),
+            "usize" => Matcher::Parse(parses::<usize>),
+            "i8" => Matcher::Parse(parses::<i8>),
+            "i16" => Matcher::Parse(parses::<i16>),
+            "i32" => Matcher::Parse(parses::<i32>),
+            "i64" => Matcher::Parse(parses::<i64>),
+            "i128" => Matcher::Parse(parses::<i128>),
+            "isize" => Matcher::Parse(parses::<isize>),
+            "f32" => Matcher::Parse(parses::<f32>),
+            "f64" => Matcher::Parse(parses::<f64>),
+            "bool" => Matcher::Parse(parses::<bool>),
+            "uuid" => Matcher::Parse(is_uuid),
+            pattern => Matcher::Pattern(Regex::new(&format!("^(?:{})$", pattern))
+                .unwrap_or_else(|error| panic!("Invalid path constraint {}: {}", pattern, error)))
+        };
+        Constraint { source: source.to_string(), matcher }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn accepts(&self, value: &str) -> bool {
+        match &self.matcher {
+            Matcher::Parse(parses) => parses(value),
+            Matcher::Pattern(regex) => regex.is_match(value)
+        }
+    }
+}
+
+fn parses<T: FromStr>(value: &str) -> bool {
+    value.parse::<T>().is_ok()
+}
+
+fn is_uuid(value: &str) -> bool {
+    value.len() == 36 && value.char_indices().all(|(index, character)| match index {
+        8 | 13 | 18 | 23 => character == '-',
+        _ => character.is_ascii_hexdigit()
+    })
+}
+
+
+// UNIT TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn type_constraints() {
+        assert!(Constraint::new("u64").accepts("42"));
+        assert!(!Constraint::new("u64").accepts("abc"));
+        assert!(!Constraint::new("u8").accepts("256"));
+        assert!(Constraint::new("uuid").accepts("67e55044-10b1-426f-9247-bb680e5fe0c8"));
+        assert!(!Constraint::new("uuid").accepts("67e55044-10b1-426f-9247"));
+    }
+
+    #[test]
+    fn pattern_constraints_match_the_whole_segment() {
+        let slug = Constraint::new("[a-z-]+");
+
+        assert!(slug.accepts("my-first-book"));
+        assert!(!slug.accepts("My-first-book"));
+        assert!(Constraint::new("[0-9]{3}").accepts("404"));
+    }
+}
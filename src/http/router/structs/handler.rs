@@ -0,0 +1,62 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use log::error;
+
+use crate::http::errors::InternalError;
+use crate::http::HttpError;
+use crate::HTTPResponse;
+
+use super::request_handler::RequestHandler;
+
+/// Error returned by a handler, turned into a response by the server `ErrorMapper`.
+pub type HandlerError = Box<dyn Error + Send + Sync>;
+
+/// Turns every error into a response, in one place: handler errors as well as the `HttpError`s
+/// raised by the server itself (bad request, not found, unauthorized...).
+pub type ErrorMapper = Arc<dyn Fn(&HandlerError) -> HTTPResponse + Send + Sync>;
+
+/// Code executed when a route matches. Implemented for every `Fn(&RequestHandler) -> R`
+/// where `R` is either an `HTTPResponse` or a `Result<HTTPResponse, E>`, so plain functions
+/// and closures capturing their environment can both be used.
+pub trait Handler: Send + Sync {
+    fn handle(&self, request: &RequestHandler) -> Result<HTTPResponse, HandlerError>;
+}
+
+impl<F, R> Handler for F
+where
+    F: Fn(&RequestHandler) -> R + Send + Sync,
+    R: IntoHandlerResult
+{
+    fn handle(&self, request: &RequestHandler) -> Result<HTTPResponse, HandlerError> {
+        self(request).into_handler_result()
+    }
+}
+
+/// What a handler may return.
+pub trait IntoHandlerResult {
+    fn into_handler_result(self) -> Result<HTTPResponse, HandlerError>;
+}
+
+impl IntoHandlerResult for HTTPResponse {
+    fn into_handler_result(self) -> Result<HTTPResponse, HandlerError> {
+        Ok(self)
+    }
+}
+
+impl<E: Into<HandlerError>> IntoHandlerResult for Result<HTTPResponse, E> {
+    fn into_handler_result(self) -> Result<HTTPResponse, HandlerError> {
+        self.map_err(Into::into)
+    }
+}
+
+/// Maps an `HttpError` to its response, anything else to a 500.
+pub fn default_error_mapper(error: &HandlerError) -> HTTPResponse {
+    match error.downcast_ref::<HttpError>() {
+        Some(http_error) => HTTPResponse::from(http_error.clone()),
+        None => {
+            error!("Handler failed: {}", error);
+            HTTPResponse::from(InternalError::from("Internal Server Error"))
+        }
+    }
+}
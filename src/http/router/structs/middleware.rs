@@ -0,0 +1,20 @@
+use std::sync::Arc;
+
+use crate::{HTTPRequest, HTTPResponse};
+
+/// Code wrapped around route execution, registered for the whole server or for a single route.
+///
+/// Middlewares run in registration order before the handler and in reverse order after it.
+/// When `before` returns a response, the handler and the remaining middlewares are skipped,
+/// and only the `after` of the middlewares that already ran are applied.
+pub trait Middleware: Send + Sync {
+    fn before(&self, _request: &mut HTTPRequest) -> Option<HTTPResponse> {
+        None
+    }
+
+    fn after(&self, _request: &HTTPRequest, response: HTTPResponse) -> HTTPResponse {
+        response
+    }
+}
+
+pub type Middlewares = Vec<Arc<dyn Middleware>>;
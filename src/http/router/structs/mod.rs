@@ -1,6 +1,16 @@
 mod route;
+mod builder;
+mod handler;
+mod middleware;
+mod segment;
+mod constraint;
 mod request_handler;
 
 pub use route::Routes;
 pub use route::Route;
-pub use request_handler::RequestHandler;
\ No newline at end of file
+pub use builder::Router;
+pub use request_handler::RequestHandler;
+pub use middleware::{Middleware, Middlewares};
+pub use segment::Segment;
+pub use constraint::Constraint;
+pub use handler::{default_error_mapper, ErrorMapper, Handler, HandlerError, IntoHandlerResult};
\ No newline at end of file
@@ -1,10 +1,15 @@
 use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
 
-use crate::{http::HTTPRequest, Route};
+use crate::{http::{cookie::{parse_cookies, Cookies}, security::principal::{Claims, Principal}, HTTPRequest, HttpError}, CookieKey, Route, State};
+
+use super::segment::Segment;
 
 pub struct RequestHandler {
     request: HTTPRequest,
-    path_params: PathParams
+    path_params: PathParams,
+    state: State
 }
 
 type PathParams = HashMap<String, String>;
@@ -19,25 +24,87 @@ impl RequestHandler {
         self.path_params.clone()
     } 
 
-    pub fn body(&self) -> Option<String> {
+    /// The path parameter parsed as `T`, failing with a bad request when missing or invalid.
+    pub fn path_param<T: FromStr>(&self, name: &str) -> Result<T, HttpError> {
+        self.path_params.get(name)
+            .ok_or(HttpError::BadRequest(format!("Missing path parameter {}", name)))?
+            .parse::<T>()
+            .map_err(|_| HttpError::BadRequest(format!("Invalid path parameter {}", name)))
+    } 
+
+    pub fn body(&self) -> Option<Vec<u8>> {
         self.request.body.clone()
     } 
+
+    /// Value of type `T` registered with `HttpServer::with_state`.
+    pub fn state<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        self.state.get::<T>()
+    }
+
+    pub(crate) fn with_state(mut self, state: State) -> Self {
+        self.state = state;
+        self
+    }
+
+    pub fn cookies(&self) -> Cookies {
+        parse_cookies(&self.request.headers.clone().unwrap_or_default())
+    }
+
+    pub fn cookie(&self, name: &str) -> Option<String> {
+        self.cookies().remove(name)
+    }
+
+    /// Value of a cookie signed with `CookieKey::sign`, ignored when the signature does not match.
+    pub fn signed_cookie(&self, name: &str, key: &CookieKey) -> Option<String> {
+        self.cookie(name).and_then(|value| key.verify(name, &value))
+    }
+
+    /// Value of a cookie encrypted with `CookieKey::encrypt`, ignored when it cannot be decrypted.
+    pub fn private_cookie(&self, name: &str, key: &CookieKey) -> Option<String> {
+        self.cookie(name).and_then(|value| key.decrypt(name, &value))
+    }
+
+    /// The caller authenticated by the route security, if any.
+    pub fn principal(&self) -> Option<Principal> {
+        self.request.principal.clone()
+    }
+
+    /// Claims of the token the caller authenticated with, when the route is secured by a JWT.
+    pub fn claims(&self) -> Option<Claims> {
+        self.request.principal.as_ref()
+            .map(|principal| principal.claims.clone())
+            .filter(|claims| !claims.is_empty())
+    }
+
+    /// The body decoded as UTF-8, failing with a bad request when it is not valid text.
+    pub fn text(&self) -> Result<Option<String>, HttpError> {
+        self.request.body.clone()
+            .map(String::from_utf8)
+            .transpose()
+            .map_err(|error| HttpError::BadRequest(error.to_string()))
+    } 
 }
 
 impl From<(&HTTPRequest, &Route)> for RequestHandler {
     fn from(value: (&HTTPRequest, &Route)) -> Self {
         RequestHandler {
             request: value.0.clone(),
-            path_params: extract_path_params(&value.0.resource, &value.1.route)
+            path_params: extract_path_params(&value.0.resource, &value.1.route),
+            state: State::default()
         }
     }
 }
 
-fn extract_path_params(request: &String, template: &String) -> PathParams {
+fn extract_path_params(request: &str, template: &str) -> PathParams {
     let split_request = request.split("/").collect::<Vec<&str>>();
     template.split("/").enumerate()
-        .filter(|(_, key)| key.starts_with("{") && key.ends_with("}"))
-        .map(|(index, key)| (key.to_string().drain(1..key.len()-1).collect(), split_request.get(index).unwrap().to_string()))
+        .filter_map(|(index, key)| match Segment::from(key) {
+            Segment::Static(_) => None,
+            Segment::Parameter(name, _) => split_request.get(index)
+                .map(|value| (name.to_string(), value.to_string())),
+            Segment::Wildcard(name) => split_request.get(index..)
+                .map(|rest| (name.to_string(), rest.join("/")))
+        })
         .collect::<PathParams>()
 }
 
@@ -55,8 +122,56 @@ mod tests {
         expected.insert("id".to_string(), "3".to_string());
         expected.insert("name".to_string(), "tata".to_string());
 
-        let result = extract_path_params(&request.to_string(),&template.to_string());
+        let result = extract_path_params(request, template);
         assert_eq!(result,expected)
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn extract_path_params_wildcard_keeps_slashes() {
+        let result = extract_path_params("/static/css/site/main.css", "/static/{*path}");
+        let anonymous = extract_path_params("/files/a/b", "/files/**");
+
+        assert_eq!(result.get("path"), Some(&"css/site/main.css".to_string()));
+        assert_eq!(anonymous.get("*"), Some(&"a/b".to_string()));
+    }
+
+    #[test]
+    fn path_param_is_typed() {
+        let request = HTTPRequest { resource: "/book/42".to_string(), ..Default::default() };
+        let route = Route { route: "/book/{id:u64}".to_string(), ..Route::default() };
+
+        let handler = RequestHandler::from((&request, &route));
+
+        assert_eq!(handler.path_param::<u64>("id"), Ok(42));
+        assert!(handler.path_param::<bool>("id").is_err());
+        assert!(handler.path_param::<u64>("name").is_err());
+    }
+
+    #[test]
+    fn text_fails_only_on_invalid_utf8() {
+        let text = HTTPRequest { body: Some(b"toto".to_vec()), ..Default::default() };
+        let binary = HTTPRequest { body: Some(vec![0xff, 0xfe]), ..Default::default() };
+
+        let text = RequestHandler::from((&text, &Route::default()));
+        let binary = RequestHandler::from((&binary, &Route::default()));
+
+        assert_eq!(text.text(), Ok(Some("toto".to_string())));
+        assert_eq!(binary.body(), Some(vec![0xff, 0xfe]));
+        assert!(binary.text().is_err());
+    }
+
+    #[test]
+    fn cookies_are_parsed_and_verified() {
+        let key = CookieKey::new(b"secret");
+        let signed = key.sign(crate::Cookie::new("user", "toto").unwrap());
+        let header = format!("theme=dark; user={}; forged=tata.c2lnbmF0dXJl", signed.value());
+        let request = HTTPRequest { headers: Some(vec![("Cookie".to_string(), header)]), ..Default::default() };
+
+        let handler = RequestHandler::from((&request, &Route::default()));
+
+        assert_eq!(handler.cookie("theme"), Some("dark".to_string()));
+        assert_eq!(handler.signed_cookie("user", &key), Some("toto".to_string()));
+        assert_eq!(handler.signed_cookie("forged", &key), None);
+        assert_eq!(handler.private_cookie("user", &key), None);
+    }
+}
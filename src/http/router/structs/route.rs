@@ -1,40 +1,101 @@
-use crate::{HTTPResponse, ResponseBuilder, Verb};
+use std::fmt;
+use std::sync::Arc;
 
+use crate::{HTTPResponse, ResponseBuilder, StatusCode, Verb};
+
+use super::handler::Handler;
+use super::middleware::{Middleware, Middlewares};
 use super::request_handler::RequestHandler;
 
-#[derive(PartialEq, Eq,Debug)]
+#[derive(Clone)]
 pub struct Route{
     pub verb  : Verb,
     pub route:  String,
-    pub method : fn(&RequestHandler) -> HTTPResponse,
-    pub need_security: bool
+    pub method : Arc<dyn Handler>,
+    pub need_security: bool,
+    pub security: Vec<String>,
+    pub roles: Vec<String>,
+    pub scopes: Vec<String>,
+    pub middlewares: Middlewares
 }
 
 impl Route {
-    pub fn new(verb: &Verb, route: &str, method: fn(&RequestHandler) -> HTTPResponse, need_security: bool) -> Route {
+    pub fn new<H: Handler + 'static>(verb: &Verb, route: &str, method: H, need_security: bool) -> Route {
         Route {
             verb: *verb,
             route: route.to_string(), 
-            method,
-            need_security
+            method: Arc::new(method),
+            need_security,
+            security: vec![],
+            roles: vec![],
+            scopes: vec![],
+            middlewares: vec![]
         }
     }
+
+    /// Requires one of the named schemes registered on `Config`, instead of the default security.
+    pub fn with_security_scheme(mut self, scheme: &str) -> Route {
+        self.need_security = true;
+        self.security.push(scheme.to_string());
+        self
+    }
+
+    /// Only lets through callers having at least one of these roles.
+    pub fn with_roles(mut self, roles: &[&str]) -> Route {
+        self.need_security = true;
+        self.roles = roles.iter().map(|role| role.to_string()).collect();
+        self
+    }
+
+    /// Only lets through callers granted all of these scopes.
+    pub fn with_scopes(mut self, scopes: &[&str]) -> Route {
+        self.need_security = true;
+        self.scopes = scopes.iter().map(|scope| scope.to_string()).collect();
+        self
+    }
+
+    /// Adds a middleware running only around this route, inside the server wide ones.
+    pub fn with_middleware<M: Middleware + 'static>(mut self, middleware: M) -> Route {
+        self.middlewares.push(Arc::new(middleware));
+        self
+    }
+}
+
+/// Routes are compared on what they match, handlers cannot be compared.
+impl PartialEq for Route {
+    fn eq(&self, other: &Self) -> bool {
+        self.verb == other.verb
+            && self.route == other.route
+            && self.need_security == other.need_security
+            && self.security == other.security
+            && self.roles == other.roles
+            && self.scopes == other.scopes
+    }
 }
 
-impl Clone for Route {
-    fn clone(&self) -> Self {
-        Route { route: self.route.clone(), ..*self }
+impl Eq for Route {}
+
+impl fmt::Debug for Route {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Route")
+            .field("verb", &self.verb)
+            .field("route", &self.route)
+            .field("need_security", &self.need_security)
+            .field("security", &self.security)
+            .field("roles", &self.roles)
+            .field("scopes", &self.scopes)
+            .finish()
     }
 }
 
 impl Default for Route {
     fn default() -> Self {
-        Self { verb: Verb::GET, route: "/".to_string(), method: default_method, need_security: false }
+        Route::new(&Verb::GET, "/", default_method, false)
     }
 }
 
 fn default_method(_: &RequestHandler) -> HTTPResponse {
-    ResponseBuilder::new(200, Some("default".to_string())).build()
+    ResponseBuilder::new(StatusCode::OK, Some("default".to_string())).build()
 }
 
-pub type Routes = Vec<Route>;
\ No newline at end of file
+pub type Routes = Vec<Route>;
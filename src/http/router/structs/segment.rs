@@ -0,0 +1,45 @@
+/// One `/` separated part of a route template.
+#[derive(PartialEq, Eq, Debug)]
+pub enum Segment<'a> {
+    /// Matched literally.
+    Static(&'a str),
+    /// `{name}` or `{name:constraint}`: matches any single segment accepted by the constraint.
+    Parameter(&'a str, Option<&'a str>),
+    /// `{*name}` or `**`: matches the rest of the path, slashes included. Only allowed last.
+    /// The anonymous `**` is captured under the `*` name.
+    Wildcard(&'a str)
+}
+
+impl<'a> From<&'a str> for Segment<'a> {
+    fn from(segment: &'a str) -> Self {
+        if segment == "**" {
+            return Segment::Wildcard("*");
+        }
+        match segment.strip_prefix('{').and_then(|segment| segment.strip_suffix('}')) {
+            Some(name) => match name.strip_prefix('*') {
+                Some(name) => Segment::Wildcard(name),
+                None => match name.split_once(':') {
+                    Some((name, constraint)) => Segment::Parameter(name, Some(constraint)),
+                    None => Segment::Parameter(name, None)
+                }
+            },
+            None => Segment::Static(segment)
+        }
+    }
+}
+
+
+// UNIT TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_segments() {
+        assert_eq!(Segment::from("books"), Segment::Static("books"));
+        assert_eq!(Segment::from("{id}"), Segment::Parameter("id", None));
+        assert_eq!(Segment::from("{code:[0-9]{3}}"), Segment::Parameter("code", Some("[0-9]{3}")));
+        assert_eq!(Segment::from("{*path}"), Segment::Wildcard("path"));
+        assert_eq!(Segment::from("**"), Segment::Wildcard("*"));
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+
+use crate::{Route, Routes, Verb};
+
+use super::structs::{Constraint, Segment};
+
+/// Routes compiled into a prefix tree of path segments, built once when the server is created.
+///
+/// Lookup walks one node per segment. At each level a static segment is tried first, then
+/// constrained `{parameter:constraint}` accepting the segment, then unconstrained `{parameter}`,
+/// then a tail wildcard, falling back to the next one when a branch leads nowhere, so the result
+/// does not depend on the order in which routes were declared.
+#[derive(Default)]
+pub struct RouteTree {
+    root: Node
+}
+
+#[derive(Default)]
+struct Node {
+    statics: HashMap<String, Node>,
+    parameters: Vec<(Option<Constraint>, Node)>,
+    wildcard: Option<Box<Node>>,
+    routes: Routes
+}
+
+impl RouteTree {
+    /// # Panics
+    ///
+    /// Panics if a wildcard is not the last segment of the route, or if a constraint is invalid.
+    pub fn insert(&mut self, route: Route) {
+        let segments = route.route.split('/').collect::<Vec<&str>>();
+        let mut node = &mut self.root;
+        for (index, segment) in segments.iter().enumerate() {
+            node = match Segment::from(*segment) {
+                Segment::Static(segment) => node.statics.entry(segment.to_string()).or_default(),
+                Segment::Parameter(_, constraint) => node.parameter(constraint),
+                Segment::Wildcard(_) => {
+                    assert!(index == segments.len() - 1, "Wildcard must be the last segment of {}", route.route);
+                    node.wildcard.get_or_insert_with(Box::default)
+                }
+            };
+        }
+        node.routes.push(route);
+    }
+
+    pub fn find(&self, verb: &Verb, path: &str) -> Option<&Route> {
+        let segments = path.split('/').collect::<Vec<&str>>();
+        self.root.find(&segments, verb)
+    }
+
+    /// Every verb registered for a route matching the path, whatever its precedence.
+    pub fn allowed_verbs(&self, path: &str) -> Vec<Verb> {
+        let segments = path.split('/').collect::<Vec<&str>>();
+        let mut verbs = vec![];
+        self.root.collect_verbs(&segments, &mut verbs);
+        verbs
+    }
+}
+
+impl Node {
+    /// Child for the parameter, constrained ones being kept before the unconstrained one.
+    fn parameter(&mut self, constraint: Option<&str>) -> &mut Node {
+        let position = self.parameters.iter()
+            .position(|(existing, _)| existing.as_ref().map(Constraint::source) == constraint);
+        let index = match position {
+            Some(index) => index,
+            None => {
+                let index = match constraint {
+                    Some(_) => self.parameters.iter().take_while(|(existing, _)| existing.is_some()).count(),
+                    None => self.parameters.len()
+                };
+                self.parameters.insert(index, (constraint.map(Constraint::new), Node::default()));
+                index
+            }
+        };
+        &mut self.parameters[index].1
+    }
+
+    fn find(&self, segments: &[&str], verb: &Verb) -> Option<&Route> {
+        match segments.split_first() {
+            None => self.routes.iter().find(|route| route.verb == *verb),
+            Some((segment, rest)) => self.statics
+                .get(*segment)
+                .and_then(|node| node.find(rest, verb))
+                .or_else(|| self.parameters.iter()
+                    .filter(|(constraint, _)| constraint.as_ref().is_none_or(|constraint| constraint.accepts(segment)))
+                    .find_map(|(_, node)| node.find(rest, verb)))
+                .or_else(|| self.wildcard.as_ref().and_then(|node| node.find(&[], verb)))
+        }
+    }
+}
+
+impl Node {
+    fn collect_verbs(&self, segments: &[&str], verbs: &mut Vec<Verb>) {
+        match segments.split_first() {
+            None => self.routes.iter()
+                .for_each(|route| if !verbs.contains(&route.verb) { verbs.push(route.verb) }),
+            Some((segment, rest)) => {
+                if let Some(node) = self.statics.get(*segment) {
+                    node.collect_verbs(rest, verbs);
+                }
+                self.parameters.iter()
+                    .filter(|(constraint, _)| constraint.as_ref().is_none_or(|constraint| constraint.accepts(segment)))
+                    .for_each(|(_, node)| node.collect_verbs(rest, verbs));
+                if let Some(node) = &self.wildcard {
+                    node.collect_verbs(&[], verbs);
+                }
+            }
+        }
+    }
+}
+
+impl From<&Routes> for RouteTree {
+    fn from(routes: &Routes) -> Self {
+        let mut tree = RouteTree::default();
+        routes.iter().cloned().for_each(|route| tree.insert(route));
+        tree
+    }
+}
+
+
+// UNIT TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(templates: &[&str]) -> RouteTree {
+        RouteTree::from(&templates.iter()
+            .map(|template| Route { route: template.to_string(), ..Route::default() })
+            .collect::<Routes>())
+    }
+
+    fn matched(tree: &RouteTree, path: &str) -> Option<String> {
+        tree.find(&Verb::GET, path).map(|route| route.route.clone())
+    }
+
+    #[test]
+    fn find_combinations() {
+        let tree = tree(&["ressource/id/toto/{dd}", "ressource/{id}/toto/{dd}"]);
+
+        assert_eq!(matched(&tree, "ressource/1/toto/2"), Some("ressource/{id}/toto/{dd}".to_string()));
+        assert_eq!(matched(&tree, "ressource/1/toto/2?test=12"), Some("ressource/{id}/toto/{dd}".to_string()));
+        assert_eq!(matched(&tree, "ressource/1/toto"), None);
+        assert_eq!(matched(&tree, "ressources/1/toto/2"), None);
+    }
+
+    #[test]
+    fn static_segment_beats_parameter_whatever_the_order() {
+        let tree = tree(&["/users/{id}", "/users/me"]);
+
+        assert_eq!(matched(&tree, "/users/me"), Some("/users/me".to_string()));
+        assert_eq!(matched(&tree, "/users/12"), Some("/users/{id}".to_string()));
+    }
+
+    #[test]
+    fn falls_back_to_parameter_when_static_branch_fails() {
+        let tree = tree(&["/users/me/settings", "/users/{id}/books"]);
+
+        assert_eq!(matched(&tree, "/users/me/books"), Some("/users/{id}/books".to_string()));
+    }
+
+    #[test]
+    fn wildcard_captures_the_rest_with_lowest_precedence() {
+        let tree = tree(&["/static/{*path}", "/static/{file}", "/static/index.html", "/files/**"]);
+
+        assert_eq!(matched(&tree, "/static/index.html"), Some("/static/index.html".to_string()));
+        assert_eq!(matched(&tree, "/static/app.js"), Some("/static/{file}".to_string()));
+        assert_eq!(matched(&tree, "/static/css/site.css"), Some("/static/{*path}".to_string()));
+        assert_eq!(matched(&tree, "/files/a/b/c"), Some("/files/**".to_string()));
+        assert_eq!(matched(&tree, "/files"), None);
+    }
+
+    #[test]
+    fn constrained_parameters_fall_through() {
+        let tree = tree(&["/book/{slug}", "/book/{id:u64}", "/book/{isbn:[0-9]{3}-[0-9]+}", "/book/{id:u64}/pages"]);
+
+        assert_eq!(matched(&tree, "/book/42"), Some("/book/{id:u64}".to_string()));
+        assert_eq!(matched(&tree, "/book/978-2070"), Some("/book/{isbn:[0-9]{3}-[0-9]+}".to_string()));
+        assert_eq!(matched(&tree, "/book/abc"), Some("/book/{slug}".to_string()));
+        assert_eq!(matched(&tree, "/book/42/pages"), Some("/book/{id:u64}/pages".to_string()));
+        assert_eq!(matched(&tree, "/book/abc/pages"), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn wildcard_must_be_last() {
+        tree(&["/static/{*path}/edit"]);
+    }
+
+    #[test]
+    fn allowed_verbs_of_every_matching_route() {
+        let tree = RouteTree::from(&vec![
+            Route { verb: Verb::GET, route: "/book/{id}".to_string(), ..Route::default() },
+            Route { verb: Verb::DELETE, route: "/book/{id:u64}".to_string(), ..Route::default() },
+            Route { verb: Verb::POST, route: "/book".to_string(), ..Route::default() },
+        ]);
+
+        assert_eq!(tree.allowed_verbs("/book/42"), vec![Verb::DELETE, Verb::GET]);
+        assert_eq!(tree.allowed_verbs("/book/abc"), vec![Verb::GET]);
+        assert_eq!(tree.allowed_verbs("/books"), vec![]);
+    }
+
+    #[test]
+    fn find_filters_on_verb() {
+        let tree = RouteTree::from(&vec![Route { verb: Verb::POST, ..Route::default() }, Route::default()]);
+
+        assert_eq!(tree.find(&Verb::POST, "/").map(|route| route.verb), Some(Verb::POST));
+        assert_eq!(tree.find(&Verb::PUT, "/"), None);
+    }
+}
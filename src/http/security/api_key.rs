@@ -0,0 +1,134 @@
+use std::sync::Arc;
+
+use subtle::ConstantTimeEq;
+
+use crate::{http::HttpError, HTTPRequest};
+
+use super::principal::Principal;
+
+/// Finds the caller owning an API key, if any.
+pub type ApiKeyMethod = Arc<dyn Fn(&str) -> Option<Principal> + Send + Sync>;
+
+/// Where the client sends its key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeyLocation {
+    Header(String),
+    Query(String)
+}
+
+/// A key of the in-memory keystore. The caller is identified by the key id, never by the key itself.
+#[derive(Clone)]
+pub struct ApiKey {
+    id: String,
+    key: String,
+    scopes: Vec<String>
+}
+
+impl ApiKey {
+    pub fn new(id: &str, key: &str) -> ApiKey {
+        ApiKey { id: id.to_string(), key: key.to_string(), scopes: vec![] }
+    }
+
+    pub fn with_scopes(mut self, scopes: &[&str]) -> ApiKey {
+        self.scopes = scopes.iter().map(|scope| scope.to_string()).collect();
+        self
+    }
+
+    fn principal(&self) -> Principal {
+        Principal { username: self.id.clone(), scopes: self.scopes.clone(), ..Default::default() }
+    }
+}
+
+#[derive(Clone)]
+enum Validator {
+    Method(ApiKeyMethod),
+    Keystore(Vec<ApiKey>)
+}
+
+/// Authentication by a static key, checked with a callback or against a keystore.
+#[derive(Clone)]
+pub struct ApiKeyAuth {
+    location: KeyLocation,
+    validator: Validator
+}
+
+impl ApiKeyAuth {
+    pub fn new<F>(location: &KeyLocation, validate: F) -> ApiKeyAuth
+    where
+        F: Fn(&str) -> Option<Principal> + Send + Sync + 'static
+    {
+        ApiKeyAuth { location: location.clone(), validator: Validator::Method(Arc::new(validate)) }
+    }
+
+    /// Accepts only the keys of the keystore, compared in constant time.
+    pub fn keystore(location: &KeyLocation, keys: &[ApiKey]) -> ApiKeyAuth {
+        ApiKeyAuth { location: location.clone(), validator: Validator::Keystore(keys.to_vec()) }
+    }
+
+    pub(super) fn authenticate(&self, request: &HTTPRequest) -> Result<Principal, HttpError> {
+        let key = self.extract_key(request)
+            .ok_or(HttpError::UnauthorizedError("Missing API key".to_string()))?;
+
+        match &self.validator {
+            Validator::Method(validate) => validate(&key),
+            Validator::Keystore(keys) => keys.iter()
+                .fold(None, |found, known| {
+                    let same : bool = key.as_bytes().ct_eq(known.key.as_bytes()).into();
+                    found.or(same.then(|| known.principal()))
+                })
+        }.ok_or(HttpError::UnauthorizedError("Invalid API key".to_string()))
+    }
+
+    fn extract_key(&self, request: &HTTPRequest) -> Option<String> {
+        match &self.location {
+            KeyLocation::Header(name) => request.get_header(name).map(|(_, value)| value),
+            KeyLocation::Query(name) => request.query_params.as_ref()
+                .and_then(|params| params.get(name).cloned())
+        }.filter(|key| !key.is_empty())
+    }
+}
+
+
+// UNIT TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keystore(location: KeyLocation) -> ApiKeyAuth {
+        ApiKeyAuth::keystore(&location, &[
+            ApiKey::new("reporting", "k-1").with_scopes(&["read"]),
+            ApiKey::new("billing", "k-2").with_scopes(&["read", "write"])
+        ])
+    }
+
+    #[test]
+    fn authenticate_finds_the_key_in_a_header() {
+        let request = HTTPRequest::try_from("GET / HTTP/1.1\r\nX-Api-Key: k-2\r\n\r\n").unwrap();
+
+        let principal = keystore(KeyLocation::Header("X-Api-Key".to_string())).authenticate(&request).unwrap();
+
+        assert_eq!(principal.username, "billing");
+        assert_eq!(principal.scopes, vec!["read".to_string(), "write".to_string()]);
+    }
+
+    #[test]
+    fn authenticate_finds_the_key_in_the_query() {
+        let request = HTTPRequest::try_from("GET /?api_key=k-1 HTTP/1.1\r\n\r\n").unwrap();
+        let unknown = HTTPRequest::try_from("GET /?api_key=k-3 HTTP/1.1\r\n\r\n").unwrap();
+        let auth = keystore(KeyLocation::Query("api_key".to_string()));
+
+        assert_eq!(auth.authenticate(&request).map(|principal| principal.username), Ok("reporting".to_string()));
+        assert_eq!(auth.authenticate(&unknown), Err(HttpError::UnauthorizedError("Invalid API key".to_string())));
+    }
+
+    #[test]
+    fn authenticate_with_a_callback() {
+        let location = KeyLocation::Header("X-Api-Key".to_string());
+        let auth = ApiKeyAuth::new(&location, |key| (key == "toto").then(|| Principal::new("toto")));
+        let missing = HTTPRequest::try_from("GET / HTTP/1.1\r\n\r\n").unwrap();
+        let valid = HTTPRequest::try_from("GET / HTTP/1.1\r\nx-api-key: toto\r\n\r\n").unwrap();
+
+        assert_eq!(auth.authenticate(&missing), Err(HttpError::UnauthorizedError("Missing API key".to_string())));
+        assert_eq!(auth.authenticate(&valid), Ok(Principal::new("toto")));
+    }
+}
@@ -0,0 +1,182 @@
+use std::sync::Arc;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use subtle::ConstantTimeEq;
+
+use crate::{http::HttpError, HTTPRequest};
+
+use super::principal::Principal;
+
+type Username = String;
+type Password = String;
+pub type AuthMethod = fn((Username, Password)) -> bool;
+/// Finds the caller, with their roles and scopes, owning the username and password, if any.
+pub type IdentifyMethod = Arc<dyn Fn(&str, &str) -> Option<Principal> + Send + Sync>;
+
+const SCHEME : &str = "Basic";
+const UTF_8 : &str = "UTF-8";
+
+#[derive(Clone)]
+enum Validator {
+    Method(AuthMethod),
+    Identify(IdentifyMethod),
+    Credentials(Vec<(Username, Password)>)
+}
+
+/// RFC 7617 Basic authentication, checking the credentials with a callback or against a fixed list.
+#[derive(Clone)]
+pub struct BasicAuth {
+    validator: Validator,
+    realm: String,
+    charset: Option<String>
+}
+
+impl BasicAuth {
+    pub fn new(validate: AuthMethod) -> BasicAuth {
+        BasicAuth::with_validator(Validator::Method(validate))
+    }
+
+    /// Checks the credentials with a callback also giving the caller roles and scopes,
+    /// which routes requiring some need: the other validators only know the username.
+    pub fn identify<F>(identify: F) -> BasicAuth
+    where
+        F: Fn(&str, &str) -> Option<Principal> + Send + Sync + 'static
+    {
+        BasicAuth::with_validator(Validator::Identify(Arc::new(identify)))
+    }
+
+    /// Accepts only these credentials, compared in constant time.
+    pub fn credentials(credentials: &[(&str, &str)]) -> BasicAuth {
+        BasicAuth::with_validator(Validator::Credentials(credentials.iter()
+            .map(|(username, password)| (username.to_string(), password.to_string()))
+            .collect()))
+    }
+
+    fn with_validator(validator: Validator) -> BasicAuth {
+        BasicAuth { validator, realm: "ilmen".to_string(), charset: None }
+    }
+
+    pub fn with_realm(&mut self, realm: &str) -> &mut Self {
+        self.realm = realm.to_string();
+        self
+    }
+
+    /// Charset advertised in the challenge. Without `UTF-8`, credentials that are not valid UTF-8
+    /// are read as ISO-8859-1.
+    pub fn with_charset(&mut self, charset: &str) -> &mut Self {
+        self.charset = Some(charset.to_string());
+        self
+    }
+
+    pub(super) fn authenticate(&self, request: &HTTPRequest) -> Result<Principal, HttpError> {
+        let (username, password) = self.credentials_of(request)?;
+
+        self.validate(&username, &password)
+            .ok_or(self.challenge("Unauthorized"))
+    }
+
+    fn credentials_of(&self, request: &HTTPRequest) -> Result<(Username, Password), HttpError> {
+        let (_, value) = request.get_header("Authorization")
+            .ok_or(self.challenge("Missing header"))?;
+        let (scheme, encoded) = value.split_once(' ')
+            .ok_or(self.challenge("No user password provided"))?;
+
+        if !scheme.eq_ignore_ascii_case(SCHEME) {
+            return Err(self.challenge("Wrong Protocol"));
+        }
+
+        let decoded = STANDARD.decode(encoded.trim())
+            .map_err(|_| self.challenge("Authentication parameter is not base64 encoded"))?;
+        self.decode_text(decoded)?
+            .split_once(':')
+            .map(|(username, password)| (username.to_string(), password.to_string()))
+            .ok_or(self.challenge("No user password provided"))
+    }
+
+    fn decode_text(&self, bytes: Vec<u8>) -> Result<String, HttpError> {
+        match String::from_utf8(bytes) {
+            Ok(text) => Ok(text),
+            Err(_) if self.is_utf8() => Err(self.challenge("Credentials are not valid UTF-8")),
+            Err(error) => Ok(error.into_bytes().into_iter().map(char::from).collect())
+        }
+    }
+
+    fn validate(&self, username: &str, password: &str) -> Option<Principal> {
+        let valid = match &self.validator {
+            Validator::Method(validate) => validate((username.to_string(), password.to_string())),
+            Validator::Identify(identify) => return identify(username, password),
+            Validator::Credentials(credentials) => credentials.iter()
+                .fold(0u8, |matched, (known_username, known_password)| {
+                    let same = username.as_bytes().ct_eq(known_username.as_bytes()) & password.as_bytes().ct_eq(known_password.as_bytes());
+                    matched | same.unwrap_u8()
+                }) == 1
+        };
+        valid.then(|| Principal::new(username))
+    }
+
+    fn is_utf8(&self) -> bool {
+        self.charset.as_ref().is_some_and(|charset| charset.eq_ignore_ascii_case(UTF_8))
+    }
+
+    fn challenge(&self, reason: &str) -> HttpError {
+        let charset = self.charset.as_ref()
+            .map(|charset| format!(", charset=\"{}\"", charset))
+            .unwrap_or_default();
+        HttpError::AuthenticationRequired(format!("{} realm=\"{}\"{}", SCHEME, self.realm, charset), reason.to_string())
+    }
+}
+
+
+// UNIT TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(authorization: &str) -> HTTPRequest {
+        HTTPRequest::try_from(format!("GET / HTTP/1.1\r\nAuthorization: {}\r\n\r\n", authorization).as_str()).unwrap()
+    }
+
+    #[test]
+    fn authenticate_decodes_the_standard_alphabet() {
+        // "toto:ab?>" encodes to "dG90bzphYj8+", not valid in the URL safe alphabet
+        let basic = BasicAuth::credentials(&[("toto", "ab?>")]);
+
+        assert_eq!(basic.authenticate(&request("basic dG90bzphYj8+")), Ok(Principal::new("toto")));
+        assert!(basic.authenticate(&request("Basic dG90bzphYj8-")).is_err());
+    }
+
+    #[test]
+    fn authenticate_checks_every_credential() {
+        let basic = BasicAuth::credentials(&[("admin", "admin"), ("toto", "tata")]);
+
+        assert!(basic.authenticate(&request("Basic dG90bzp0YXRh")).is_ok());
+        assert_eq!(BasicAuth::credentials(&[("toto", "toto")]).authenticate(&request("Basic dG90bzp0YXRh")),
+            Err(HttpError::AuthenticationRequired("Basic realm=\"ilmen\"".to_string(), "Unauthorized".to_string())));
+    }
+
+    #[test]
+    fn authenticate_with_an_identifying_callback() {
+        let basic = BasicAuth::identify(|username, password| (password == "tata").then(|| Principal::new(username).with_roles(&["admin"])));
+
+        assert_eq!(basic.authenticate(&request("Basic dG90bzp0YXRh")), Ok(Principal::new("toto").with_roles(&["admin"])));
+        assert_eq!(basic.authenticate(&request("Basic dG90bzp0b3Rv")),
+            Err(HttpError::AuthenticationRequired("Basic realm=\"ilmen\"".to_string(), "Unauthorized".to_string())));
+    }
+
+    #[test]
+    fn authenticate_challenges_with_realm_and_charset() {
+        let basic = BasicAuth::new(|_| true).with_realm("admin").with_charset("UTF-8").to_owned();
+
+        assert_eq!(basic.authenticate(&request("Bearer toto")),
+            Err(HttpError::AuthenticationRequired("Basic realm=\"admin\", charset=\"UTF-8\"".to_string(), "Wrong Protocol".to_string())));
+    }
+
+    #[test]
+    fn authenticate_reads_latin_1_unless_utf_8_is_required() {
+        // "t\u{e9}:x" encoded as ISO-8859-1
+        let latin = request("Basic dOk6eA==");
+
+        assert_eq!(BasicAuth::new(|_| true).authenticate(&latin), Ok(Principal::new("t\u{e9}")));
+        assert!(BasicAuth::new(|_| true).with_charset("UTF-8").authenticate(&latin).is_err());
+    }
+}
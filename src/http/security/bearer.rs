@@ -0,0 +1,58 @@
+use std::sync::Arc;
+
+use crate::{http::HttpError, HTTPRequest};
+
+use super::principal::Principal;
+
+/// Validates a bearer token, returning the caller it belongs to or the reason it is refused.
+pub type BearerMethod = Arc<dyn Fn(&str) -> Result<Principal, String> + Send + Sync>;
+
+const SCHEME : &str = "Bearer";
+
+/// RFC 6750 authentication: a request without credentials gets a bare challenge, a refused token
+/// an `invalid_token` one.
+pub(super) fn bearer_auth(request: &HTTPRequest, validate: &BearerMethod) -> Result<Principal, HttpError> {
+    let token = extract_token(request)
+        .ok_or(HttpError::AuthenticationRequired(SCHEME.to_string(), "Missing bearer token".to_string()))?;
+
+    validate(&token)
+        .map_err(|reason| HttpError::AuthenticationRequired(format!("{} error=\"invalid_token\"", SCHEME), reason))
+}
+
+fn extract_token(request: &HTTPRequest) -> Option<String> {
+    request.get_header("Authorization")
+        .and_then(|(_, value)| value.split_once(' ')
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case(SCHEME))
+            .map(|(_, token)| token.trim().to_string()))
+        .filter(|token| !token.is_empty())
+}
+
+
+// UNIT TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate() -> BearerMethod {
+        Arc::new(|token| match token {
+            "secret" => Ok(Principal::new("toto")),
+            _ => Err("Unknown token".to_string())
+        })
+    }
+
+    #[test]
+    fn bearer_auth_returns_the_principal() {
+        let request = HTTPRequest::try_from("GET / HTTP/1.1\r\nAuthorization: bearer secret\r\n\r\n").unwrap();
+
+        assert_eq!(bearer_auth(&request, &validate()), Ok(Principal::new("toto")));
+    }
+
+    #[test]
+    fn bearer_auth_challenges_missing_or_invalid_tokens() {
+        let missing = HTTPRequest::try_from("GET / HTTP/1.1\r\nAuthorization: Basic dG90bzp0YXRh\r\n\r\n").unwrap();
+        let invalid = HTTPRequest::try_from("GET / HTTP/1.1\r\nAuthorization: Bearer toto\r\n\r\n").unwrap();
+
+        assert_eq!(bearer_auth(&missing, &validate()), Err(HttpError::AuthenticationRequired("Bearer".to_string(), "Missing bearer token".to_string())));
+        assert_eq!(bearer_auth(&invalid, &validate()), Err(HttpError::AuthenticationRequired("Bearer error=\"invalid_token\"".to_string(), "Unknown token".to_string())));
+    }
+}
@@ -1 +1,14 @@
-pub mod service;
\ No newline at end of file
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub mod service;
+pub mod basic;
+pub mod bearer;
+pub mod jwt;
+pub mod api_key;
+pub mod signature;
+pub mod principal;
+
+/// Seconds since epoch, for the schemes checking token or request dates.
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_secs()).unwrap_or_default()
+}
@@ -0,0 +1,31 @@
+use serde_json::{Map, Value};
+
+pub type Claims = Map<String, Value>;
+
+/// The authenticated caller, as returned by a security scheme.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Principal {
+    pub username: String,
+    pub roles: Vec<String>,
+    pub scopes: Vec<String>,
+    pub claims: Claims
+}
+
+impl Principal {
+    pub fn new(username: &str) -> Principal {
+        Principal { username: username.to_string(), ..Default::default() }
+    }
+
+    pub fn with_roles(mut self, roles: &[&str]) -> Principal {
+        self.roles = roles.iter().map(|role| role.to_string()).collect();
+        self
+    }
+
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|granted| granted == role)
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|granted| granted == scope)
+    }
+}
@@ -1,68 +1,129 @@
-use anyhow::Context;
-use base64::{engine::general_purpose::URL_SAFE, Engine};
-use std::str;
-use crate::{http::HttpError, HTTPRequest, Route};
+use std::sync::Arc;
+use crate::{http::HttpError, Config, HTTPRequest, Route};
 
-pub fn apply_security(request: &HTTPRequest, route: Route, security: SecurityProtocol) -> Result<Route, HttpError> {
-    match security {
-        SecurityProtocol::None => Ok(()),
-        SecurityProtocol::Basic(validate_methode) => base_auth(request, validate_methode),
-    }.map(|_| route)
+use super::api_key::ApiKeyAuth;
+use super::basic::{AuthMethod, BasicAuth};
+use super::bearer::{bearer_auth, BearerMethod};
+use super::jwt::JwtVerifier;
+use super::principal::Principal;
+use super::signature::HmacSignature;
+
+/// Authenticates the request against the schemes required by the route, returning the caller
+/// when the accepting scheme identifies one.
+///
+/// Routes without `need_security` are let through. Routes naming no scheme use the server
+/// default one, the others pass as soon as one of their schemes accepts the request, the next
+/// schemes not being tried. When all of them refuse it, the 401 carries all their challenges.
+pub fn apply_security(request: &HTTPRequest, route: &Route, config: &Config) -> Result<Option<Principal>, HttpError> {
+    if !route.need_security {
+        return Ok(None);
+    }
+
+    let schemes = match route.security.is_empty() {
+        true => vec![config.security()],
+        false => route.security.iter()
+            .map(|name| config.security_scheme(name).ok_or(HttpError::DefaultError))
+            .collect::<Result<Vec<SecurityProtocol>, HttpError>>()?
+    };
+
+    let mut errors = vec![];
+    for security in schemes {
+        match authenticate(request, security) {
+            Ok(principal) => {
+                authorize(route, principal.as_ref())?;
+                return Ok(principal);
+            },
+            Err(error) => errors.push(error)
+        }
+    }
+    Err(combine(errors))
 }
 
-fn base_auth(request: &HTTPRequest, validate_methode: AuthMethod) -> Result<(), HttpError>{
-    let header_auth_value : Vec<String> = request.get_header("Authorization")
-        .ok_or(HttpError::UnauthorizedError("Missing header".to_string()))?
-        .1
-        .split(' ')
-        .map(str::to_owned)
-        .collect();
-
-    let is_basic_protocol = header_auth_value.first()
-        .ok_or(HttpError::UnauthorizedError("No protocol specified".to_string()))
-        .map(|protocol| protocol.eq(&"Basic"))?;
-
-    if !is_basic_protocol {
-        return Err(HttpError::UnauthorizedError("Wrong Protocol".to_string()));
-    }
-
-    header_auth_value
-            .get(1)
-            .context("No user password provided")
-            .and_then(decode_base64_auth)
-            .map_err(|e| HttpError::UnauthorizedError(e.to_string()))
-            .map(validate_methode)
-            .and_then(|is_valid_creds| {
-                match is_valid_creds {
-                    true => Ok(()),
-                    false => Err(HttpError::UnauthorizedError("Unauthorized".to_string())),
-                }
-            })
+/// Merges the refusals of several schemes, keeping every `WWW-Authenticate` challenge.
+fn combine(errors: Vec<HttpError>) -> HttpError {
+    let (challenges, reasons) : (Vec<String>, Vec<String>) = errors.iter()
+        .filter_map(|error| match error {
+            HttpError::AuthenticationRequired(challenge, reason) => Some((challenge.clone(), reason.clone())),
+            _ => None
+        })
+        .unzip();
+
+    match challenges.is_empty() {
+        true => errors.into_iter().last().unwrap_or(HttpError::DefaultError),
+        false => HttpError::AuthenticationRequired(challenges.join(", "), reasons.join("; "))
+    }
 }
 
-type Username = String;
-type Password = String;
-type AuthMethod = fn((Username, Password)) -> bool;
-
-fn decode_base64_auth(b64_value: &String) -> anyhow::Result<(String, String)>{
-    URL_SAFE.decode(b64_value)
-            .context("Authentication parameter is not base64 encrypted".to_string())
-            .and_then(|vect| String::from_utf8(vect).context("Could not parse from utf8"))
-            .map(|decoded | {
-                let splitter = decoded.split_once(':').unwrap_or(("", ""));
-                (splitter.0.to_string(), splitter.1.to_string())
-            })
+/// Checks the authenticated caller has one of the route roles and all of its scopes.
+/// A scheme identifying no caller cannot grant any of them, the request stays unauthenticated.
+fn authorize(route: &Route, principal: Option<&Principal>) -> Result<(), HttpError> {
+    if route.roles.is_empty() && route.scopes.is_empty() {
+        return Ok(());
+    }
+
+    let principal = principal.ok_or(HttpError::UnauthorizedError("No authenticated caller to authorize".to_string()))?;
+    let allowed = (route.roles.is_empty() || route.roles.iter().any(|role| principal.has_role(role)))
+        && route.scopes.iter().all(|scope| principal.has_scope(scope));
+    match allowed {
+        true => Ok(()),
+        false => Err(HttpError::Forbidden("Insufficient permissions".to_string()))
+    }
+}
+
+fn authenticate(request: &HTTPRequest, security: SecurityProtocol) -> Result<Option<Principal>, HttpError> {
+    match security {
+        SecurityProtocol::None => Ok(None),
+        SecurityProtocol::Basic(basic) => basic.authenticate(request).map(Some),
+        SecurityProtocol::Bearer(validate) => bearer_auth(request, &validate).map(Some),
+        SecurityProtocol::ApiKey(api_key) => api_key.authenticate(request).map(Some),
+        SecurityProtocol::Signature(signature) => signature.verify(request).map(|_| None),
+    }
 }
 
 #[derive(Clone)]
 pub enum SecurityProtocol {
     None,
-    Basic(AuthMethod)
+    Basic(BasicAuth),
+    Bearer(BearerMethod),
+    ApiKey(ApiKeyAuth),
+    Signature(HmacSignature)
+}
+
+impl SecurityProtocol {
+    /// Basic scheme with the default realm, checking the credentials with the callback.
+    /// The caller gets no role nor scope, see `BasicAuth::identify` for routes requiring some.
+    pub fn basic(validate: AuthMethod) -> SecurityProtocol {
+        SecurityProtocol::Basic(BasicAuth::new(validate))
+    }
+
+    pub fn bearer<F>(validate: F) -> SecurityProtocol
+    where
+        F: Fn(&str) -> Result<Principal, String> + Send + Sync + 'static
+    {
+        SecurityProtocol::Bearer(Arc::new(validate))
+    }
+
+    /// Bearer scheme accepting the JSON Web Tokens validated by the verifier.
+    pub fn jwt(verifier: &JwtVerifier) -> SecurityProtocol {
+        let verifier = verifier.clone();
+        SecurityProtocol::bearer(move |token| verifier.verify(token))
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
     use super::*;
+    use crate::http::HTTPResponse;
+
+    fn secured() -> Route {
+        Route { need_security: true, ..Route::default() }
+    }
+
+    fn config(security: SecurityProtocol) -> Config {
+        Config::default().with_security(&security).to_owned()
+    }
 
     #[test]
     fn apply_basic_security_with_good_creds() {
@@ -71,8 +132,8 @@ mod tests {
         let  request = HTTPRequest::try_from(buffer).unwrap();
         let validate : AuthMethod= |_| true;
 
-        let result = apply_security(&request, Route::default(), SecurityProtocol::Basic(validate)).unwrap();
-        assert_eq!(result, Route::default())
+        let result = apply_security(&request, &secured(), &config(SecurityProtocol::basic(validate))).unwrap();
+        assert_eq!(result, Some(Principal::new("toto")))
     }
 
     #[test]
@@ -83,8 +144,8 @@ mod tests {
 
         let validate : AuthMethod = |_| false;
 
-        let result = apply_security(&request, Route::default(), SecurityProtocol::Basic(validate)).unwrap_err();
-        assert_eq!(result, HttpError::UnauthorizedError("Unauthorized".to_string()))
+        let result = apply_security(&request, &secured(), &config(SecurityProtocol::basic(validate))).unwrap_err();
+        assert_eq!(result, HttpError::AuthenticationRequired("Basic realm=\"ilmen\"".to_string(), "Unauthorized".to_string()))
     }
 
     #[test]
@@ -95,7 +156,115 @@ mod tests {
 
         let validate : AuthMethod = |_| false;
 
-        let result = apply_security(&request, Route::default(), SecurityProtocol::Basic(validate)).unwrap_err();
-        assert_eq!(result, HttpError::UnauthorizedError("Wrong Protocol".to_string()))
+        let result = apply_security(&request, &secured(), &config(SecurityProtocol::basic(validate))).unwrap_err();
+        assert_eq!(result, HttpError::AuthenticationRequired("Basic realm=\"ilmen\"".to_string(), "Wrong Protocol".to_string()))
+    }
+
+    #[test]
+    fn apply_security_skips_unsecured_routes() {
+        let request = HTTPRequest::try_from("GET rappel/1 HTTP/1.1\r\n\r\n").unwrap();
+        let validate : AuthMethod = |_| false;
+
+        let result = apply_security(&request, &Route::default(), &config(SecurityProtocol::basic(validate)));
+
+        assert_eq!(result, Ok(None));
+    }
+
+    #[test]
+    fn apply_security_accepts_any_of_the_route_schemes() {
+        let buffer = "GET rappel/1 HTTP/1.1\r\nAuthorization: Basic dG90bzp0YXRh\r\n\r\n";
+        let request = HTTPRequest::try_from(buffer).unwrap();
+        let admin : AuthMethod = |(user, _)| user == "admin";
+        let toto : AuthMethod = |(user, _)| user == "toto";
+        let config = Config::default()
+            .with_security_scheme("admin", &SecurityProtocol::basic(admin))
+            .with_security_scheme("toto", &SecurityProtocol::basic(toto))
+            .to_owned();
+
+        let admin_only = Route::default().with_security_scheme("admin");
+        let admin_or_toto = Route::default().with_security_scheme("admin").with_security_scheme("toto");
+
+        assert!(apply_security(&request, &admin_only, &config).is_err());
+        assert!(apply_security(&request, &admin_or_toto, &config).is_ok());
+    }
+
+    #[test]
+    fn apply_security_checks_roles_and_scopes() {
+        let request = HTTPRequest::try_from("GET rappel/1 HTTP/1.1\r\nAuthorization: Bearer toto\r\n\r\n").unwrap();
+        let config = config(SecurityProtocol::bearer(|token| {
+            let mut principal = Principal::new(token).with_roles(&["editor"]);
+            principal.scopes = vec!["read".to_string()];
+            Ok(principal)
+        }));
+        let forbidden = Err(HttpError::Forbidden("Insufficient permissions".to_string()));
+
+        assert!(apply_security(&request, &Route::default().with_roles(&["admin", "editor"]), &config).is_ok());
+        assert!(apply_security(&request, &Route::default().with_scopes(&["read"]), &config).is_ok());
+        assert_eq!(apply_security(&request, &Route::default().with_roles(&["admin"]), &config), forbidden);
+        assert_eq!(apply_security(&request, &Route::default().with_scopes(&["read", "write"]), &config), forbidden);
+    }
+
+    #[test]
+    fn apply_security_checks_roles_of_basic_callers() {
+        let request = HTTPRequest::try_from("GET rappel/1 HTTP/1.1\r\nAuthorization: Basic dG90bzp0YXRh\r\n\r\n").unwrap();
+        let identifying = config(SecurityProtocol::Basic(BasicAuth::identify(|username, _| Some(Principal::new(username).with_roles(&["editor"])))));
+        let validating = config(SecurityProtocol::basic(|_| true));
+        let editors = Route::default().with_roles(&["editor"]);
+
+        assert_eq!(apply_security(&request, &editors, &identifying), Ok(Some(Principal::new("toto").with_roles(&["editor"]))));
+        assert_eq!(apply_security(&request, &editors, &validating), Err(HttpError::Forbidden("Insufficient permissions".to_string())));
+    }
+
+    #[test]
+    fn apply_security_without_identity_is_unauthorized() {
+        let request = HTTPRequest::try_from("GET rappel/1 HTTP/1.1\r\n\r\n").unwrap();
+
+        let result = apply_security(&request, &Route::default().with_roles(&["admin"]), &Config::default());
+
+        assert_eq!(result, Err(HttpError::UnauthorizedError("No authenticated caller to authorize".to_string())));
+    }
+
+    #[test]
+    fn apply_security_stops_at_the_first_accepting_scheme() {
+        static CALLS : AtomicUsize = AtomicUsize::new(0);
+        let request = HTTPRequest::try_from("GET rappel/1 HTTP/1.1\r\nAuthorization: Bearer toto\r\n\r\n").unwrap();
+        let counting = SecurityProtocol::bearer(|token| {
+            CALLS.fetch_add(1, Ordering::SeqCst);
+            Ok(Principal::new(token))
+        });
+        let config = Config::default()
+            .with_security_scheme("first", &counting)
+            .with_security_scheme("second", &counting)
+            .to_owned();
+
+        let route = Route::default().with_security_scheme("first").with_security_scheme("second");
+
+        assert!(apply_security(&request, &route, &config).is_ok());
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn apply_security_sends_the_challenges_of_every_scheme() {
+        let request = HTTPRequest::try_from("GET rappel/1 HTTP/1.1\r\n\r\n").unwrap();
+        let config = Config::default()
+            .with_security_scheme("basic", &SecurityProtocol::basic(|_| true))
+            .with_security_scheme("bearer", &SecurityProtocol::bearer(|token| Ok(Principal::new(token))))
+            .to_owned();
+
+        let route = Route::default().with_security_scheme("basic").with_security_scheme("bearer");
+        let response = HTTPResponse::from(apply_security(&request, &route, &config).unwrap_err());
+
+        assert_eq!(response.get_header("WWW-Authenticate"), Some("Basic realm=\"ilmen\", Bearer".to_string()));
+    }
+
+    #[test]
+    fn apply_bearer_security() {
+        let request = HTTPRequest::try_from("GET rappel/1 HTTP/1.1\r\nAuthorization: Bearer secret\r\n\r\n").unwrap();
+        let accepting = config(SecurityProtocol::bearer(|token| Ok(Principal::new(token))));
+        let refusing = config(SecurityProtocol::bearer(|_| Err("Expired".to_string())));
+
+        assert_eq!(apply_security(&request, &secured(), &accepting), Ok(Some(Principal::new("secret"))));
+        assert_eq!(HTTPResponse::from(apply_security(&request, &secured(), &refusing).unwrap_err()).get_header("WWW-Authenticate"),
+            Some("Bearer error=\"invalid_token\"".to_string()));
     }
 }
\ No newline at end of file
@@ -0,0 +1,123 @@
+use std::str;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::{http::HttpError, HTTPRequest};
+
+use super::now;
+
+const PREFIX : &str = "sha256=";
+
+/// HMAC-SHA256 signature of the raw request body, as sent by webhook providers.
+///
+/// The signature header holds `sha256=<hex digest>`. With a timestamp header, the signed payload is
+/// `<timestamp>.<body>` and requests older or newer than the window are refused as replays.
+#[derive(Clone)]
+pub struct HmacSignature {
+    secret: Vec<u8>,
+    header: String,
+    timestamp: Option<(String, u64)>
+}
+
+impl HmacSignature {
+    pub fn new(secret: &[u8]) -> HmacSignature {
+        HmacSignature { secret: secret.to_vec(), header: "X-Signature".to_string(), timestamp: None }
+    }
+
+    pub fn with_header(&mut self, header: &str) -> &mut Self {
+        self.header = header.to_string();
+        self
+    }
+
+    /// Requires a timestamp, in seconds since epoch, at most `window` seconds away from now.
+    pub fn with_timestamp(&mut self, header: &str, window: &u64) -> &mut Self {
+        self.timestamp = Some((header.to_string(), *window));
+        self
+    }
+
+    pub(super) fn verify(&self, request: &HTTPRequest) -> Result<(), HttpError> {
+        let signature = request.get_header(&self.header)
+            .and_then(|(_, value)| value.strip_prefix(PREFIX).map(str::to_string))
+            .and_then(|digest| decode_hex(&digest))
+            .ok_or(HttpError::UnauthorizedError("Missing signature".to_string()))?;
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).map_err(|_| HttpError::DefaultError)?;
+        if let Some((timestamp_header, window)) = &self.timestamp {
+            let (_, timestamp) = request.get_header(timestamp_header)
+                .ok_or(HttpError::UnauthorizedError("Missing timestamp".to_string()))?;
+            check_window(&timestamp, *window, now())?;
+            mac.update(format!("{}.", timestamp).as_bytes());
+        }
+        mac.update(request.body.as_deref().unwrap_or_default());
+
+        mac.verify_slice(&signature)
+            .map_err(|_| HttpError::UnauthorizedError("Invalid signature".to_string()))
+    }
+}
+
+fn check_window(timestamp: &str, window: u64, now: u64) -> Result<(), HttpError> {
+    timestamp.parse::<u64>()
+        .ok()
+        .filter(|timestamp| timestamp.abs_diff(now) <= window)
+        .map(|_| ())
+        .ok_or(HttpError::UnauthorizedError("Timestamp outside of the replay window".to_string()))
+}
+
+fn decode_hex(digest: &str) -> Option<Vec<u8>> {
+    digest.as_bytes()
+        .chunks(2)
+        .map(|pair| str::from_utf8(pair).ok()
+            .filter(|byte| byte.len() == 2)
+            .and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect()
+}
+
+
+// UNIT TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sign(payload: &[u8]) -> String {
+        let digest = Hmac::<Sha256>::new_from_slice(b"secret").unwrap().chain_update(payload).finalize().into_bytes();
+        PREFIX.to_string() + &digest.iter().map(|byte| format!("{:02x}", byte)).collect::<String>()
+    }
+
+    fn request(headers: &str, body: &str) -> HTTPRequest {
+        HTTPRequest::try_from(format!("POST /hook HTTP/1.1\r\n{}Content-Length: {}\r\n\r\n{}", headers, body.len(), body).as_str()).unwrap()
+    }
+
+    #[test]
+    fn verify_the_body_signature() {
+        let signed = request(&format!("X-Signature: {}\r\n", sign(b"{\"id\":1}")), "{\"id\":1}");
+        let tampered = request(&format!("X-Signature: {}\r\n", sign(b"{\"id\":1}")), "{\"id\":2}");
+        let unsigned = request("", "{\"id\":1}");
+
+        assert_eq!(HmacSignature::new(b"secret").verify(&signed), Ok(()));
+        assert_eq!(HmacSignature::new(b"other").verify(&signed), Err(HttpError::UnauthorizedError("Invalid signature".to_string())));
+        assert_eq!(HmacSignature::new(b"secret").verify(&tampered), Err(HttpError::UnauthorizedError("Invalid signature".to_string())));
+        assert_eq!(HmacSignature::new(b"secret").verify(&unsigned), Err(HttpError::UnauthorizedError("Missing signature".to_string())));
+    }
+
+    #[test]
+    fn verify_the_timestamp_is_signed_and_recent() {
+        let verifier = HmacSignature::new(b"secret").with_header("X-Hub-Signature").with_timestamp("X-Timestamp", &300).to_owned();
+        let timestamp = now().to_string();
+        let signature = sign(format!("{}.toto", timestamp).as_bytes());
+
+        let fresh = request(&format!("X-Hub-Signature: {}\r\nX-Timestamp: {}\r\n", signature, timestamp), "toto");
+        let replayed = request(&format!("X-Hub-Signature: {}\r\nX-Timestamp: {}\r\n", signature, now() - 600), "toto");
+
+        assert_eq!(verifier.verify(&fresh), Ok(()));
+        assert_eq!(verifier.verify(&replayed), Err(HttpError::UnauthorizedError("Timestamp outside of the replay window".to_string())));
+    }
+
+    #[test]
+    fn check_window_accepts_both_directions() {
+        assert!(check_window("1000", 60, 1030).is_ok());
+        assert!(check_window("1030", 60, 1000).is_ok());
+        assert!(check_window("1000", 60, 1061).is_err());
+        assert!(check_window("toto", 60, 1000).is_err());
+    }
+}
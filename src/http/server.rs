@@ -1,76 +1,336 @@
 use std::io::prelude::*;
+use std::io::BufReader;
+use std::io::BufWriter;
 use std::net::TcpListener;
 use std::net::TcpStream;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+use log::error;
 use log::info;
 use log::trace;
 
 use crate::http::router::handle_request;
-use crate::http::requests::HTTPRequest;
+use crate::http::requests::{read_request, HTTPRequest};
 use crate::http::responses::HTTPResponse;
-use crate::http::configuration::ThreadPool;
+use crate::http::configuration::{Backlog, ThreadPool};
 
-use super::router::Routes;
+use super::router::{default_error_mapper, ErrorMapper, HandlerError, Middleware, Middlewares, RouteTree, Routes};
 use super::configuration::Config;
+use super::state::State;
 
+/// How often an idle connection checks whether it should make room for waiting ones.
+const IDLE_POLL : Duration = Duration::from_millis(100);
 
-#[derive(Default)]
 pub struct HttpServer {
     configuration: Config, 
-    handler : Routes
+    handler : Arc<RouteTree>,
+    state: State,
+    error_mapper: ErrorMapper,
+    middlewares: Middlewares
+}
+
+impl Default for HttpServer {
+    fn default() -> Self {
+        HttpServer::new(Config::default(), Routes::default())
+    }
 }
 
 impl HttpServer {
 
-    pub fn new(configuration: Config, handler : Routes) -> Self {
+    /// # Panics
+    ///
+    /// Panics if a route requires a security scheme that is not registered on the configuration,
+    /// or if a route template is invalid.
+    pub fn new<R: Into<Routes>>(configuration: Config, handler : R) -> Self {
+        let handler = handler.into();
+        handler.iter()
+            .flat_map(|route| route.security.iter())
+            .for_each(|scheme| assert!(configuration.security_scheme(scheme).is_some(), "Unknown security scheme {}", scheme));
+
         HttpServer {
             configuration,
-            handler
+            handler: Arc::new(RouteTree::from(&handler)),
+            state: State::default(),
+            error_mapper: Arc::new(default_error_mapper),
+            middlewares: vec![]
         }
     } 
 
+    /// Adds a middleware running around every request, before route specific ones.
+    pub fn with_middleware<M: Middleware + 'static>(mut self, middleware: M) -> Self {
+        self.middlewares.push(Arc::new(middleware));
+        self
+    }
+
+    /// Replaces how errors returned by handlers are turned into responses.
+    /// By default an `HttpError` gives its own response and anything else a 500.
+    pub fn with_error_mapper<M>(mut self, error_mapper: M) -> Self
+    where
+        M: Fn(&HandlerError) -> HTTPResponse + Send + Sync + 'static
+    {
+        self.error_mapper = Arc::new(error_mapper);
+        self
+    }
+
+    /// Registers a value shared by every handler, retrieved by type with `RequestHandler::state`.
+    pub fn with_state<T: Send + Sync + 'static>(mut self, state: T) -> Self {
+        self.state.insert(state);
+        self
+    }
+
    pub fn start(&self) {
         info!("Opening connection and listening");
         let config = self.configuration.clone();
         info!("Start listening on {}", config.adresse());
         let listener = TcpListener::bind(config.adresse()).unwrap();
-        info!("Initializing thread pool : {}", 5);
-        let pool = ThreadPool::new(5);
+        info!("Initializing thread pool : {}", config.workers());
+        let pool = ThreadPool::new(config.workers());
         let term = Arc::new(AtomicBool::new(false));
         signal_hook::flag::register(signal_hook::consts::SIGTERM, Arc::clone(&term)).unwrap();
         while !term.load(Ordering::Relaxed) {
             for stream in listener.incoming() {
-                let stream = stream.unwrap();
-                let routes = self.handler.clone();
-                let config = config.clone();
-    
-                pool.execute(move || {
-                    handle_connection(stream, routes, config);
-                });
+                self.dispatch(stream.unwrap(), &pool);
             }
         }
    } 
+
+    fn dispatch(&self, stream: TcpStream, pool: &ThreadPool) {
+        let routes = self.handler.clone();
+        let config = self.configuration.clone();
+        let state = self.state.clone();
+        let error_mapper = self.error_mapper.clone();
+        let middlewares = self.middlewares.clone();
+        let backlog = pool.backlog();
+
+        pool.execute(move || {
+            handle_connection(stream, routes, config, state, error_mapper, middlewares, backlog);
+        });
+    }
 }
 
 
 // PRIVATE
-fn handle_connection(mut stream: TcpStream, handler : Routes, config: Config) {
-    let mut buffer: Vec<u8> = vec![0; config.request_size()];
-    stream.read(&mut buffer).unwrap();
-
-    let response = 
-    HTTPRequest::try_from(buffer)
-        .map(|request |  handle_request(&request, handler, config))
-        .unwrap_or_else(HTTPResponse::from);
-        
-            
-    trace!("Response: {}", response.to_string());
-    write(stream, response.to_string());
+/// Read half of a connection failing every read once its deadline passed, so a client cannot
+/// hold a worker by sending a byte just before each read times out.
+struct DeadlineStream {
+    stream: TcpStream,
+    deadline: Instant
+}
+
+impl Read for DeadlineStream {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(std::io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        self.stream.read(buffer)
+    }
+}
+
+fn handle_connection(stream: TcpStream, handler : Arc<RouteTree>, config: Config, state: State, error_mapper: ErrorMapper, middlewares: Middlewares, backlog: Backlog) {
+    let mut reader = match stream.try_clone() {
+        Ok(read_half) => BufReader::new(DeadlineStream { stream: read_half, deadline: Instant::now() }),
+        Err(error) => {
+            error!("Could not read from connection: {}", error);
+            return;
+        }
+    };
+    let mut writer = BufWriter::new(stream);
+
+    let mut served = 0;
+    while wait_for_request(&mut reader, config.keep_alive_timeout(), &backlog) {
+        reader.get_mut().deadline = Instant::now() + config.request_timeout();
+        let request = match read_request(&mut reader, config.request_size()).transpose() {
+            Some(request) => request,
+            None => break
+        };
+        served += 1;
+
+        let mut keep_alive = request.as_ref().is_ok_and(HTTPRequest::keep_alive) && served < config.max_requests();
+        let http_1_0 = request.as_ref().is_ok_and(|request| request.protocol == "HTTP/1.0");
+
+        let mut response = request
+            .map(|request |  handle_request(&request, &handler, config.clone(), state.clone(), &error_mapper, &middlewares))
+            .unwrap_or_else(|error| error_mapper(&error.into()));
+        if http_1_0 && response.without_chunked_encoding() {
+            keep_alive = false;
+        }
+        response.put_header("Connection", if keep_alive { "keep-alive" } else { "close" });
+        if let Some(server_name) = config.server_name() {
+            response.put_default_header("Server", &server_name);
+        }
+
+        trace!("Response: {}", response.to_string());
+        if let Err(error) = write(&mut writer, response) {
+            trace!("Could not write response: {}", error);
+            break;
+        }
+
+        if !keep_alive {
+            break;
+        }
+    }
 }
 
-fn write(mut stream : TcpStream, response: String) {
-    stream.write_all(response.as_bytes()).unwrap();
-    stream.flush().unwrap();
+/// Waits until the next request starts arriving, for at most the idle timeout. Gives up early
+/// when connections wait for a worker, an idle connection not being worth more than them.
+fn wait_for_request(reader: &mut BufReader<DeadlineStream>, timeout: Duration, backlog: &Backlog) -> bool {
+    let idle_until = Instant::now() + timeout;
+    loop {
+        let now = Instant::now();
+        reader.get_mut().deadline = idle_until.min(now + IDLE_POLL);
+        match reader.fill_buf() {
+            Ok(buffer) => return !buffer.is_empty(),
+            Err(error) if matches!(error.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => {
+                if Instant::now() >= idle_until || !backlog.is_empty() {
+                    return false;
+                }
+            },
+            Err(_) => return false
+        }
+    }
+}
+
+fn write(writer : &mut BufWriter<TcpStream>, response: HTTPResponse) -> std::io::Result<()> {
+    response.write_to(writer)?;
+    writer.flush()
+}
+
+
+// UNIT TEST
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{RequestHandler, ResponseBuilder, Route, StatusCode, Verb};
+
+    fn read_response(reader: &mut BufReader<TcpStream>) -> String {
+        let mut head = String::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            head.push_str(&line);
+            if line == "\r\n" {
+                break;
+            }
+        }
+        let length = head.lines()
+            .find_map(|line| line.strip_prefix("Content-Length: "))
+            .map(|length| length.parse::<usize>().unwrap())
+            .unwrap_or_default();
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        head + &String::from_utf8(body).unwrap()
+    }
+
+    #[test]
+    fn handle_connection_serves_several_requests_on_one_socket() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let adresse = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            handle_connection(stream, Arc::new(RouteTree::from(&vec![Route::default()])), Config::default(), State::default(), Arc::new(default_error_mapper), vec![], Backlog::default());
+        });
+
+        let mut client = TcpStream::connect(adresse).unwrap();
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+
+        client.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        let first = read_response(&mut reader);
+        assert!(first.starts_with("HTTP/1.1 200 OK"));
+        assert!(first.contains("Connection: keep-alive"));
+
+        client.write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+        let second = read_response(&mut reader);
+        assert!(second.contains("Connection: close"));
+
+        server.join().unwrap();
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "");
+    }
+
+    #[test]
+    fn handle_connection_streams_to_http_1_0_until_close() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let adresse = listener.local_addr().unwrap();
+        let route = Route::new(&Verb::GET, "/", |_: &RequestHandler|
+            ResponseBuilder::new(StatusCode::OK, None).chunks(["to", "to"].into_iter()).build(), false);
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            handle_connection(stream, Arc::new(RouteTree::from(&vec![route])), Config::default(), State::default(), Arc::new(default_error_mapper), vec![], Backlog::default());
+        });
+
+        let mut client = TcpStream::connect(adresse).unwrap();
+        client.write_all(b"GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n").unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        server.join().unwrap();
+
+        assert!(response.contains("Connection: close"));
+        assert!(!response.contains("Transfer-Encoding"));
+        assert!(response.ends_with("\r\n\r\ntoto"));
+    }
+
+    #[test]
+    fn handle_connection_makes_room_when_idle_clients_outnumber_workers() {
+        let config = Config::default().with_workers(&2).with_keep_alive_timeout(&Duration::from_secs(30)).to_owned();
+        let server = HttpServer::new(config.clone(), vec![Route::default()]);
+        let pool = ThreadPool::new(config.workers());
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let adresse = listener.local_addr().unwrap();
+
+        let mut clients = vec![];
+        for _ in 0..4 {
+            let start = Instant::now();
+            let mut client = TcpStream::connect(adresse).unwrap();
+            client.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+            server.dispatch(listener.accept().unwrap().0, &pool);
+
+            let mut reader = BufReader::new(client.try_clone().unwrap());
+            assert!(read_response(&mut reader).starts_with("HTTP/1.1 200 OK"));
+            assert!(start.elapsed() < Duration::from_secs(5));
+            clients.push(reader);
+        }
+
+        let mut rest = String::new();
+        clients[0].read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "");
+    }
+
+    #[test]
+    fn handle_connection_bounds_the_time_to_receive_a_request() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let adresse = listener.local_addr().unwrap();
+        let config = Config::default()
+            .with_keep_alive_timeout(&Duration::from_secs(30))
+            .with_request_timeout(&Duration::from_millis(300))
+            .to_owned();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            handle_connection(stream, Arc::new(RouteTree::from(&vec![Route::default()])), config, State::default(), Arc::new(default_error_mapper), vec![], Backlog::default());
+        });
+
+        let start = Instant::now();
+        let mut client = TcpStream::connect(adresse).unwrap();
+        let mut reader = client.try_clone().unwrap();
+        thread::spawn(move || {
+            for byte in b"GET / HTTP/1.1\r\nHost: localhost\r\n" {
+                if client.write_all(&[*byte]).is_err() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+        });
+        let _ = reader.read_to_end(&mut vec![]);
+        server.join().unwrap();
+
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
 }
@@ -0,0 +1,40 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Application state shared by every handler, one value per type.
+#[derive(Clone, Default)]
+pub struct State {
+    values: Arc<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>
+}
+
+impl State {
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) {
+        Arc::make_mut(&mut self.values).insert(TypeId::of::<T>(), Arc::new(value));
+    }
+
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        self.values
+            .get(&TypeId::of::<T>())
+            .cloned()
+            .and_then(|value| value.downcast::<T>().ok())
+    }
+}
+
+
+// UNIT TEST
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_returns_value_of_requested_type() {
+        let mut state = State::default();
+        state.insert(42_u32);
+        state.insert("pool".to_string());
+
+        assert_eq!(state.get::<u32>().as_deref(), Some(&42));
+        assert_eq!(state.get::<String>().as_deref(), Some(&"pool".to_string()));
+        assert!(state.get::<i64>().is_none());
+    }
+}
@@ -2,12 +2,24 @@ pub mod http;
 
 pub use http::Routes;
 pub use http::Route;
+pub use http::Router;
 pub use http::Config;
+pub use http::Cors;
 use http::HTTPRequest;
 use http::HTTPResponse;
 pub use http::ResponseBuilder;
+pub use http::StatusCode;
 pub use http::Verb;
 
 pub use http::HttpServer;
 
-pub use http::RequestHandler;
\ No newline at end of file
+pub use http::RequestHandler;
+pub use http::Handler;
+pub use http::Middleware;
+pub use http::HandlerError;
+pub use http::State;
+pub use http::security::principal::Principal;
+pub use http::cookie::Cookie;
+pub use http::cookie::CookieKey;
+pub use http::cookie::InvalidCookie;
+pub use http::cookie::SameSite;
\ No newline at end of file